@@ -1,4 +1,4 @@
-use std::fs;
+use std::{env, fs};
 
 #[derive(Clone)]
 struct Grid {
@@ -18,13 +18,41 @@ impl From<&String> for Grid {
             }
             grid.push(row)
         }
-        Grid {
+        let mut retval = Grid {
             grid,
             galaxies: Vec::new(),
             empty_rows: Vec::new(),
             empty_columns: Vec::new(),
+        };
+        retval.find_empty_space();
+        retval.pinpoint_galaxies();
+        retval
+    }
+}
+
+/// Sum of `|a - b|` over every pair of values, via sorting and a running prefix sum.
+fn sum_pairwise_differences(mut values: Vec<u64>) -> u64 {
+    values.sort_unstable();
+    let mut sum = 0;
+    let mut prefix = 0;
+    for (idx, &value) in values.iter().enumerate() {
+        sum += value * idx as u64 - prefix;
+        prefix += value;
+    }
+    sum
+}
+
+/// For each index along an axis, how many of `empty` lie strictly before it.
+fn empty_prefix(len: usize, empty: &[usize]) -> Vec<u64> {
+    let mut retval = Vec::with_capacity(len);
+    let mut count = 0;
+    for idx in 0..len {
+        retval.push(count);
+        if empty.contains(&idx) {
+            count += 1;
         }
     }
+    retval
 }
 
 impl Grid {
@@ -35,31 +63,11 @@ impl Grid {
             }
         }
         for x in 0..self.grid[0].len() {
-            let mut empty = true;
-            for y in 0..self.grid.len() {
-                if self.grid[y][x] == '#' {
-                    empty = false;
-                }
-            }
-            if empty {
+            if self.grid.iter().all(|row| row[x] != '#') {
                 self.empty_columns.push(x);
             }
         }
     }
-    fn expand(&mut self) {
-        let mut new_grid = self.grid.clone();
-        self.find_empty_space();
-        for (index, &x) in self.empty_columns.iter().enumerate() {
-            for y in 0..new_grid.len() {
-                new_grid[y].insert(x + index, '.');
-            }
-        }
-
-        for (index, &y) in self.empty_rows.iter().enumerate() {
-            new_grid.insert(y + index, vec!['.'; new_grid[y].len()])
-        }
-        self.grid = new_grid;
-    }
 
     fn pinpoint_galaxies(&mut self) {
         for y in 0..self.grid.len() {
@@ -71,75 +79,133 @@ impl Grid {
         }
     }
 
-    fn sum_distances(&self) -> usize {
-        let mut sum = 0;
-        for (idx, &(x1, y1)) in self.galaxies.iter().enumerate() {
-            for i in idx + 1..self.galaxies.len() {
-                let (x2, y2) = self.galaxies[i];
-                sum +=
-                    ((y2 as isize - y1 as isize).abs() + (x2 as isize - x1 as isize).abs()) as usize
-            }
-        }
-        sum
+    /// Galaxy coordinates after every empty row and column has grown to `factor` copies.
+    fn expanded_galaxies(&self, factor: u64) -> Vec<(u64, u64)> {
+        let growth = factor.saturating_sub(1);
+        let column_offsets = empty_prefix(self.grid[0].len(), &self.empty_columns);
+        let row_offsets = empty_prefix(self.grid.len(), &self.empty_rows);
+        self.galaxies
+            .iter()
+            .map(|&(x, y)| {
+                (
+                    x as u64 + column_offsets[x] * growth,
+                    y as u64 + row_offsets[y] * growth,
+                )
+            })
+            .collect()
+    }
+
+    fn sum_distances(&self, factor: u64) -> u64 {
+        let (xs, ys) = self.expanded_galaxies(factor).into_iter().unzip();
+        sum_pairwise_differences(xs) + sum_pairwise_differences(ys)
     }
 
-    fn sum_giant_distances(&self) -> usize {
-        let mut sum = 0;
-        for (idx, &(x1, y1)) in self.galaxies.iter().enumerate() {
-            let x1_offset = self
-                .empty_columns
-                .iter()
-                .filter(|&&col| col < x1)
-                .collect::<Vec<&usize>>()
-                .len()
-                * 999999;
-            let y1_offset = self
-                .empty_rows
-                .iter()
-                .filter(|&&col| col < y1)
-                .collect::<Vec<&usize>>()
-                .len()
-                * 999999;
-            for i in idx + 1..self.galaxies.len() {
-                let (x2, y2) = self.galaxies[i];
-                let x2_offset = self
-                    .empty_columns
-                    .iter()
-                    .filter(|&&col| col < x2)
-                    .collect::<Vec<&usize>>()
-                    .len()
-                    * 999999;
-                let y2_offset = self
-                    .empty_rows
-                    .iter()
-                    .filter(|&&col| col < y2)
-                    .collect::<Vec<&usize>>()
-                    .len()
-                    * 999999;
-                sum += (((y2 + y2_offset) as isize - (y1 + y1_offset) as isize).abs()
-                    + ((x2 + x2_offset) as isize - (x1 + x1_offset) as isize).abs())
-                    as usize
+    /// Expanded coordinates of a galaxy, numbered from 1 in reading order like the puzzle.
+    fn galaxy(&self, number: usize, factor: u64) -> Option<(u64, u64)> {
+        let galaxies = self.expanded_galaxies(factor);
+        number
+            .checked_sub(1)
+            .and_then(|idx| galaxies.get(idx).copied())
+    }
+
+    fn distance(&self, a: usize, b: usize, factor: u64) -> Option<u64> {
+        let (x1, y1) = self.galaxy(a, factor)?;
+        let (x2, y2) = self.galaxy(b, factor)?;
+        Some(x1.abs_diff(x2) + y1.abs_diff(y2))
+    }
+
+    /// Every pair of galaxies `(a, b, distance)` with `a < b`, numbered from 1.
+    fn pair_distances(&self, factor: u64) -> Vec<(usize, usize, u64)> {
+        let galaxies = self.expanded_galaxies(factor);
+        let mut retval = Vec::new();
+        for (idx, &(x1, y1)) in galaxies.iter().enumerate() {
+            for (offset, &(x2, y2)) in galaxies[idx + 1..].iter().enumerate() {
+                retval.push((idx + 1, idx + offset + 2, x1.abs_diff(x2) + y1.abs_diff(y2)));
             }
         }
-        sum
+        retval
+    }
+
+    fn farthest_pair(&self, factor: u64) -> Option<(usize, usize, u64)> {
+        self.pair_distances(factor)
+            .into_iter()
+            .max_by_key(|&(_, _, distance)| distance)
+    }
+
+    /// The `k` galaxies closest to `number` as `(galaxy, distance)`, nearest first.
+    fn nearest_neighbours(&self, number: usize, k: usize, factor: u64) -> Vec<(usize, u64)> {
+        let Some((x1, y1)) = self.galaxy(number, factor) else {
+            return Vec::new();
+        };
+        let mut retval = self
+            .expanded_galaxies(factor)
+            .into_iter()
+            .enumerate()
+            .map(|(idx, (x2, y2))| (idx + 1, x1.abs_diff(x2) + y1.abs_diff(y2)))
+            .filter(|&(other, _)| other != number)
+            .collect::<Vec<(usize, u64)>>();
+        retval.sort_by_key(|&(other, distance)| (distance, other));
+        retval.truncate(k);
+        retval
     }
 }
 
-fn part1(mut grid: Grid) {
-    grid.expand();
-    grid.pinpoint_galaxies();
-    println!("part1: {}", grid.sum_distances())
+fn part1(grid: &Grid) {
+    println!("part1: {}", grid.sum_distances(2))
 }
 
-fn part2(mut grid: Grid) {
-    grid.find_empty_space();
-    grid.pinpoint_galaxies();
-    println!("part2: {}", grid.sum_giant_distances())
+fn part2(grid: &Grid) {
+    println!("part2: {}", grid.sum_distances(1_000_000))
+}
+
+fn report(grid: &Grid, factor: u64, k: usize) {
+    for number in 1..=grid.galaxies.len() {
+        let (x, y) = grid.galaxy(number, factor).unwrap();
+        let neighbours = grid
+            .nearest_neighbours(number, k, factor)
+            .iter()
+            .map(|(other, distance)| format!("{other} ({distance})"))
+            .collect::<Vec<String>>()
+            .join(", ");
+        println!("galaxy {number} at ({x}, {y}): {neighbours}");
+    }
+    if let Some((a, b, distance)) = grid.farthest_pair(factor) {
+        println!("farthest: {a} and {b} ({distance})");
+    }
 }
 
 fn main() {
     let input = fs::read_to_string("input.txt").expect("input.txt should exist");
     let grid = Grid::from(&input);
-    part1(grid.clone());
-    part2(grid.clone());
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    match args.first().map(String::as_str) {
+        Some("distance") => {
+            let (Some(factor), Some(a), Some(b)) = (args.get(1), args.get(2), args.get(3)) else {
+                println!("usage: distance <factor> <galaxy> <galaxy>");
+                return;
+            };
+            let factor = factor.parse().expect("factor should be a number");
+            let a = a.parse().expect("galaxy should be a number");
+            let b = b.parse().expect("galaxy should be a number");
+            match grid.distance(a, b, factor) {
+                Some(distance) => println!("{a} -> {b}: {distance}"),
+                None => println!("no such galaxy"),
+            }
+        }
+        Some("neighbours") => {
+            let Some(factor) = args.get(1) else {
+                println!("usage: neighbours <factor> [k]");
+                return;
+            };
+            let factor = factor.parse().expect("factor should be a number");
+            let k = args
+                .get(2)
+                .map_or(1, |k| k.parse().expect("k should be a number"));
+            report(&grid, factor, k);
+        }
+        _ => {
+            part1(&grid);
+            part2(&grid);
+        }
+    }
 }
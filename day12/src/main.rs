@@ -1,7 +1,7 @@
-use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{env, fs};
 
-use cached::proc_macro::cached;
-use itertools::{repeat_n, Itertools};
+use rayon::prelude::*;
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
 enum SpringState {
@@ -29,17 +29,17 @@ struct Springs {
 
 impl From<&str> for Springs {
     fn from(value: &str) -> Self {
-        let mut splits = value.split(" ");
+        let mut splits = value.split(' ');
         let springs = splits
             .next()
             .unwrap()
             .chars()
-            .map(|c| SpringState::from(c))
+            .map(SpringState::from)
             .collect();
         let groups = splits
             .next()
             .unwrap()
-            .split(",")
+            .split(',')
             .map(|v| v.parse::<usize>().unwrap())
             .collect();
         Springs { springs, groups }
@@ -47,113 +47,349 @@ impl From<&str> for Springs {
 }
 
 impl Springs {
-    fn valid(&self, arrangement: &Vec<SpringState>) -> bool {
-        let mut groups = Vec::new();
-        let mut count = 0;
-        for &item in arrangement {
-            if item == SpringState::Operational && count != 0 {
-                groups.push(count);
-                count = 0;
-            } else if item == SpringState::Damaged {
-                count += 1;
+    /// Length of the run of springs that could be damaged starting at each index.
+    fn damage_runs(&self) -> Vec<usize> {
+        let mut runs = vec![0; self.springs.len() + 1];
+        for i in (0..self.springs.len()).rev() {
+            if self.springs[i] != SpringState::Operational {
+                runs[i] = runs[i + 1] + 1;
             }
         }
-        if count > 0 {
-            groups.push(count);
+        runs
+    }
+
+    /// Where the search resumes if group `j` is placed starting at index `i`, if it fits there.
+    fn place_group(&self, runs: &[usize], i: usize, j: usize) -> Option<usize> {
+        let n = self.springs.len();
+        let end = i + *self.groups.get(j)?;
+        if self.springs[i] != SpringState::Operational
+            && runs[i] >= self.groups[j]
+            && (end == n || self.springs[end] != SpringState::Damaged)
+        {
+            Some((end + 1).min(n))
+        } else {
+            None
         }
-        groups == self.groups
     }
 
-    fn valid_arrangements(&self) -> usize {
-        let mut valid_arrangements = 0;
-        let unknowns = self
-            .springs
-            .iter()
-            .filter(|&&v| v == SpringState::Unknown)
-            .collect::<Vec<&SpringState>>()
-            .len();
-        let opts = vec![SpringState::Damaged, SpringState::Operational];
-        let arrangements = repeat_n(opts, unknowns).multi_cartesian_product();
-        for arrangement in arrangements {
-            let mut local = arrangement.clone();
-            let mut test = self.springs.clone();
-            while let Some(pos) = test.iter().position(|&x| x == SpringState::Unknown) {
-                test[pos] = local.pop().unwrap();
+    /// `table[i][j]` is the number of ways `springs[i..]` can match `groups[j..]`, or `None`
+    /// if any count overflows a `u128`.
+    fn arrangement_table(&self) -> Option<Vec<Vec<u128>>> {
+        let n = self.springs.len();
+        let m = self.groups.len();
+        let runs = self.damage_runs();
+        let mut table = vec![vec![0u128; m + 1]; n + 1];
+        table[n][m] = 1;
+        for i in (0..n).rev() {
+            for j in 0..=m {
+                let mut ways = 0u128;
+                if self.springs[i] != SpringState::Damaged {
+                    ways = ways.checked_add(table[i + 1][j])?;
+                }
+                if let Some(next) = self.place_group(&runs, i, j) {
+                    ways = ways.checked_add(table[next][j + 1])?;
+                }
+                table[i][j] = ways;
+            }
+        }
+        Some(table)
+    }
+
+    fn valid_arrangements(&self) -> Option<u128> {
+        Some(self.arrangement_table()?[0][0])
+    }
+
+    /// Lazily walks every concrete arrangement as a `.#` string, unless there are too many
+    /// to count.
+    fn arrangements(&self) -> Option<Arrangements<'_>> {
+        let table = self.arrangement_table()?;
+        let stack = if table[0][0] > 0 {
+            vec![(0, 0, String::new())]
+        } else {
+            Vec::new()
+        };
+        Some(Arrangements {
+            springs: self,
+            runs: self.damage_runs(),
+            table,
+            stack,
+        })
+    }
+
+    /// The arrangement at `index` in the order produced by `arrangements`.
+    fn nth_arrangement(&self, mut index: u128) -> Option<String> {
+        let table = self.arrangement_table()?;
+        if index >= table[0][0] {
+            return None;
+        }
+        let runs = self.damage_runs();
+        let (mut i, mut j) = (0, 0);
+        let mut retval = String::new();
+        while i < self.springs.len() {
+            let operational = if self.springs[i] != SpringState::Damaged {
+                table[i + 1][j]
+            } else {
+                0
+            };
+            if index < operational {
+                retval.push('.');
+                i += 1;
+            } else {
+                index -= operational;
+                let next = self.place_group(&runs, i, j).unwrap();
+                retval.push_str(&"#".repeat(self.groups[j]));
+                if next > i + self.groups[j] {
+                    retval.push('.');
+                }
+                i = next;
+                j += 1;
             }
-            if self.valid(&test) {
-                valid_arrangements += 1;
+        }
+        Some(retval)
+    }
+
+    /// A uniformly chosen arrangement, reproducible from `seed`.
+    fn random_arrangement(&self, seed: u64) -> Option<String> {
+        let count = self.valid_arrangements()?;
+        if count == 0 {
+            return None;
+        }
+        // xorshift64*, twice over to cover the u128 range
+        let mut state = seed | 1;
+        let mut next = || {
+            state ^= state >> 12;
+            state ^= state << 25;
+            state ^= state >> 27;
+            state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+        };
+        let value = ((next() as u128) << 64) | next() as u128;
+        self.nth_arrangement(value % count)
+    }
+
+    /// For each cell, `Some(state)` when every arrangement agrees on it, or `None` if the
+    /// arrangements are too many to count.
+    fn forced_cells(&self) -> Option<Vec<Option<SpringState>>> {
+        let n = self.springs.len();
+        let m = self.groups.len();
+        let runs = self.damage_runs();
+        let table = self.arrangement_table()?;
+        let total = table[0][0];
+        if total == 0 {
+            return Some(vec![None; n]);
+        }
+        // forward[i][j] counts the ways of reaching state (i, j) from the start
+        let mut forward = vec![vec![0u128; m + 1]; n + 1];
+        forward[0][0] = 1;
+        // difference array of how many arrangements have each cell damaged
+        let mut damaged = vec![0i128; n + 1];
+        for i in 0..n {
+            for j in 0..=m {
+                let ways = forward[i][j];
+                if ways == 0 {
+                    continue;
+                }
+                if self.springs[i] != SpringState::Damaged {
+                    forward[i + 1][j] = forward[i + 1][j].checked_add(ways)?;
+                }
+                if let Some(next) = self.place_group(&runs, i, j) {
+                    forward[next][j + 1] = forward[next][j + 1].checked_add(ways)?;
+                    let completions = i128::try_from(ways.checked_mul(table[next][j + 1])?).ok()?;
+                    damaged[i] = damaged[i].checked_add(completions)?;
+                    damaged[i + self.groups[j]] =
+                        damaged[i + self.groups[j]].checked_sub(completions)?;
+                }
             }
         }
-        valid_arrangements
+        let mut retval = Vec::with_capacity(n);
+        let mut count = 0;
+        for delta in damaged.into_iter().take(n) {
+            count += delta;
+            retval.push(match u128::try_from(count).ok()? {
+                0 => Some(SpringState::Operational),
+                c if c == total => Some(SpringState::Damaged),
+                _ => None,
+            });
+        }
+        Some(retval)
     }
 
-    fn unfold(&mut self) {
+    /// Repeats the row `times` times, joining the copies with `separator`.
+    fn unfold(&mut self, times: usize, separator: SpringState) {
         let mut new_springs = Vec::new();
         let mut new_groups = Vec::new();
-        for _ in 0..4 {
-            new_springs.append(&mut self.springs.clone());
-            new_springs.push(SpringState::Unknown);
-            new_groups.append(&mut self.groups.clone());
+        for copy in 0..times {
+            if copy != 0 {
+                new_springs.push(separator);
+            }
+            new_springs.extend_from_slice(&self.springs);
+            new_groups.extend_from_slice(&self.groups);
         }
-        new_springs.append(&mut self.springs.clone());
-        new_groups.append(&mut self.groups.clone());
         self.springs = new_springs;
         self.groups = new_groups
     }
 }
 
-fn part1(input: &Vec<Springs>) {
-    let mut sum = 0;
-    for item in input {
-        sum += item.valid_arrangements()
-    }
-    println!("part1: {}", sum)
+struct Arrangements<'a> {
+    springs: &'a Springs,
+    runs: Vec<usize>,
+    table: Vec<Vec<u128>>,
+    stack: Vec<(usize, usize, String)>,
 }
 
-#[cached]
-fn valid_arrangements_inner(springs: Vec<SpringState>, groups: Vec<usize>) -> usize {
-    let first = groups[0];
-    let rest = &groups[1..];
-    let after = rest.iter().sum::<usize>() + rest.len();
-    let mut count = 0;
+impl Iterator for Arrangements<'_> {
+    type Item = String;
 
-    for before in 0..(springs.len() - after - first + 1) {
-        if springs[before..before + first]
-            .iter()
-            .all(|x| [SpringState::Damaged, SpringState::Unknown].contains(x))
-        {
-            if rest.len() == 0 {
-                if springs[before + first..]
-                    .iter()
-                    .all(|x| [SpringState::Operational, SpringState::Unknown].contains(x))
-                {
-                    count += 1
+    fn next(&mut self) -> Option<Self::Item> {
+        let springs = self.springs;
+        while let Some((i, j, prefix)) = self.stack.pop() {
+            if i == springs.springs.len() {
+                return Some(prefix);
+            }
+            // push the damaged branch first so the operational one is explored first
+            if let Some(next) = springs.place_group(&self.runs, i, j) {
+                if self.table[next][j + 1] > 0 {
+                    let mut damaged = prefix.clone();
+                    damaged.push_str(&"#".repeat(springs.groups[j]));
+                    if next > i + springs.groups[j] {
+                        damaged.push('.');
+                    }
+                    self.stack.push((next, j + 1, damaged));
                 }
-            } else if [SpringState::Operational, SpringState::Unknown]
-                .contains(&springs[before + first])
-            {
-                count +=
-                    valid_arrangements_inner(springs[before + first + 1..].to_vec(), rest.to_vec())
+            }
+            if springs.springs[i] != SpringState::Damaged && self.table[i + 1][j] > 0 {
+                let mut operational = prefix;
+                operational.push('.');
+                self.stack.push((i + 1, j, operational));
             }
         }
-        if ![SpringState::Operational, SpringState::Unknown].contains(&springs[before]) {
-            break;
-        }
+        None
+    }
+}
+
+fn explain(springs: &Springs, limit: usize, seed: u64) {
+    let row = springs
+        .springs
+        .iter()
+        .map(|state| match state {
+            SpringState::Operational => '.',
+            SpringState::Damaged => '#',
+            SpringState::Unknown => '?',
+        })
+        .collect::<String>();
+    println!("row:     {row} {:?}", springs.groups);
+    let (Some(count), Some(forced), Some(arrangements)) = (
+        springs.valid_arrangements(),
+        springs.forced_cells(),
+        springs.arrangements(),
+    ) else {
+        println!("count:   overflow");
+        return;
+    };
+    let forced = forced
+        .iter()
+        .map(|state| match state {
+            Some(SpringState::Operational) => '.',
+            Some(SpringState::Damaged) => '#',
+            _ => '?',
+        })
+        .collect::<String>();
+    println!("forced:  {forced}");
+    println!("count:   {count}");
+    if let Some(sample) = springs.random_arrangement(seed) {
+        println!("sample:  {sample}");
+    }
+    for arrangement in arrangements.take(limit) {
+        println!("         {arrangement}");
     }
+}
 
-    count
+fn gcd(a: u128, b: u128) -> u128 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
 }
 
-fn valid_arrangements(item: &Springs) -> usize {
-    valid_arrangements_inner(item.springs.clone(), item.groups.clone())
+/// How a row's arrangement counts grow as it is unfolded more times.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Progression {
+    /// Each count is the previous one times `p / q`.
+    Geometric {
+        p: u128,
+        q: u128,
+    },
+    NotGeometric,
+    /// The counts are too large to compare exactly.
+    Overflow,
+    /// Fewer than two counts, so there is no ratio to check.
+    TooShort,
+}
+
+/// Whether `counts` form a geometric progression.
+fn progression(counts: &[u128]) -> Progression {
+    if counts.len() < 2 {
+        return Progression::TooShort;
+    }
+    if counts.contains(&0) {
+        return Progression::NotGeometric;
+    }
+    let divisor = gcd(counts[1], counts[0]);
+    let (p, q) = (counts[1] / divisor, counts[0] / divisor);
+    for w in counts.windows(2) {
+        match (w[1].checked_mul(q), w[0].checked_mul(p)) {
+            (Some(l), Some(r)) if l == r => {}
+            (Some(_), Some(_)) => return Progression::NotGeometric,
+            _ => return Progression::Overflow,
+        }
+    }
+    Progression::Geometric { p, q }
 }
 
-fn part2(input: &Vec<Springs>) {
-    let mut sum = 0;
-    for item in input {
-        sum += valid_arrangements(item);
+fn growth(springs: &[Springs], max_times: usize, separator: SpringState) {
+    for (idx, row) in springs.iter().enumerate() {
+        let counts = (1..=max_times)
+            .map_while(|times| {
+                let mut unfolded = row.clone();
+                unfolded.unfold(times, separator);
+                unfolded.valid_arrangements()
+            })
+            .collect::<Vec<u128>>();
+        // counts stop at the first factor whose total overflows a u128
+        let verdict = match progression(&counts) {
+            _ if counts.len() < max_times => "overflow".to_string(),
+            Progression::Geometric { p, q: 1 } => format!("geometric x{p}"),
+            Progression::Geometric { p, q } => format!("geometric x{p}/{q}"),
+            Progression::NotGeometric => "NOT geometric".to_string(),
+            Progression::Overflow => "overflow".to_string(),
+            Progression::TooShort => "need >=2 factors".to_string(),
+        };
+        let counts = counts
+            .iter()
+            .map(u128::to_string)
+            .collect::<Vec<String>>()
+            .join(" ");
+        println!("row {}: {counts} ({verdict})", idx + 1);
     }
-    println!("part2: {}", sum);
+}
+
+fn total_arrangements(input: &[Springs]) -> Option<u128> {
+    input
+        .par_iter()
+        .map(Springs::valid_arrangements)
+        .try_reduce(|| 0, u128::checked_add)
+}
+
+fn part1(input: &[Springs]) -> Option<u128> {
+    total_arrangements(input)
+}
+
+fn part2(input: &[Springs]) -> Option<u128> {
+    let mut unfolded_springs = input.to_vec();
+    unfolded_springs
+        .iter_mut()
+        .for_each(|v| v.unfold(5, SpringState::Unknown));
+    total_arrangements(&unfolded_springs)
 }
 
 fn main() {
@@ -162,8 +398,142 @@ fn main() {
     for line in input.lines() {
         springs.push(Springs::from(line))
     }
-    part1(&springs);
-    let mut unfolded_springs = springs.clone();
-    unfolded_springs.iter_mut().for_each(|v| v.unfold());
-    part2(&unfolded_springs);
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    if args.first().map(String::as_str) == Some("explain") {
+        let row = args
+            .get(1)
+            .and_then(|row| row.parse::<usize>().ok())
+            .and_then(|row| row.checked_sub(1))
+            .and_then(|idx| springs.get(idx));
+        let Some(row) = row else {
+            println!("usage: explain <row, from 1> [limit] [seed]");
+            return;
+        };
+        let limit = args
+            .get(2)
+            .map_or(10, |l| l.parse().expect("limit should be a number"));
+        let seed = args.get(3).map_or_else(
+            || {
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .expect("clock should be after the epoch")
+                    .as_nanos() as u64
+            },
+            |s| s.parse().expect("seed should be a number"),
+        );
+        explain(row, limit, seed);
+        return;
+    }
+    if args.first().map(String::as_str) == Some("growth") {
+        let max_times = args.get(1).and_then(|t| t.parse().ok());
+        let separator = match args.get(2).map(String::as_str) {
+            None | Some("?") => Some(SpringState::Unknown),
+            Some(".") => Some(SpringState::Operational),
+            Some("#") => Some(SpringState::Damaged),
+            _ => None,
+        };
+        let (Some(max_times), Some(separator)) = (max_times, separator) else {
+            println!("usage: growth <max factor> [separator, one of .#?]");
+            return;
+        };
+        growth(&springs, max_times, separator);
+        return;
+    }
+    match part1(&springs) {
+        Some(total) => println!("part1: {total}"),
+        None => println!("part1: overflow"),
+    }
+    match part2(&springs) {
+        Some(total) => println!("part2: {total}"),
+        None => println!("part2: overflow"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    fn read_springs() -> Vec<Springs> {
+        let input = fs::read_to_string("test.txt").expect("test.txt should exist");
+        input.lines().map(Springs::from).collect()
+    }
+
+    #[test]
+    fn test_p1() {
+        assert_eq!(Some(21), part1(&read_springs()));
+    }
+
+    #[test]
+    fn test_p2() {
+        assert_eq!(Some(525152), part2(&read_springs()));
+    }
+
+    #[test]
+    fn test_progression() {
+        assert_eq!(
+            Progression::Geometric { p: 3, q: 2 },
+            progression(&[4, 6, 9])
+        );
+        assert_eq!(Progression::NotGeometric, progression(&[1, 2, 5]));
+        assert_eq!(Progression::Overflow, progression(&[3, u128::MAX - 1]));
+        assert_eq!(Progression::TooShort, progression(&[4]));
+    }
+
+    #[test]
+    fn test_arrangements() {
+        let row = &read_springs()[5];
+        let arrangements = row.arrangements().unwrap().collect::<HashSet<String>>();
+        assert_eq!(10, arrangements.len());
+        for arrangement in &arrangements {
+            let springs = Springs::from(format!("{arrangement} 0").as_str());
+            let groups = springs
+                .springs
+                .split(|state| *state == SpringState::Operational)
+                .map(<[SpringState]>::len)
+                .filter(|len| *len > 0)
+                .collect::<Vec<usize>>();
+            assert_eq!(row.groups, groups);
+        }
+        for (idx, arrangement) in row.arrangements().unwrap().enumerate() {
+            assert_eq!(Some(arrangement), row.nth_arrangement(idx as u128));
+        }
+        assert_eq!(None, row.nth_arrangement(10));
+        assert!(arrangements.contains(&row.random_arrangement(2023).unwrap()));
+    }
+
+    #[test]
+    fn test_forced_cells() {
+        let row = &read_springs()[5];
+        let forced = row
+            .forced_cells()
+            .unwrap()
+            .iter()
+            .map(|state| match state {
+                Some(SpringState::Operational) => '.',
+                Some(SpringState::Damaged) => '#',
+                _ => '?',
+            })
+            .collect::<String>();
+        assert_eq!(".###.???????", forced);
+    }
+
+    #[test]
+    fn test_overflow() {
+        let row = Springs::from("???????????????????? 1,1");
+        let overflow = (1..=40).find(|&times| {
+            let mut unfolded = row.clone();
+            unfolded.unfold(times, SpringState::Unknown);
+            unfolded.valid_arrangements().is_none()
+        });
+        let times = overflow.expect("counts should overflow before 40 copies");
+        let mut unfolded = row.clone();
+        unfolded.unfold(times, SpringState::Unknown);
+        assert!(unfolded.forced_cells().is_none());
+        assert!(unfolded.arrangements().is_none());
+        unfolded = row.clone();
+        unfolded.unfold(times - 1, SpringState::Unknown);
+        assert!(unfolded.valid_arrangements().is_some());
+    }
 }
@@ -1,7 +1,4 @@
-use std::collections::HashSet;
-use std::fs;
-
-use itertools::Itertools;
+use std::{env, fs};
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
 enum Axis {
@@ -9,158 +6,201 @@ enum Axis {
     Vertical,
 }
 
-#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+#[derive(Clone, Eq, PartialEq, Debug, Hash)]
 struct Reflection {
     axis: Axis,
     index: usize,
+    /// How many rows or columns on each side take part in the reflection.
+    extent: usize,
+    /// Cells `(x, y)` on the top or left of the mirror that had to change for it to hold.
+    smudges: Vec<(usize, usize)>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Reflections {
+    horizontal: Vec<Reflection>,
+    vertical: Vec<Reflection>,
+}
+
+impl Reflections {
+    fn iter(&self) -> impl Iterator<Item = &Reflection> {
+        self.horizontal.iter().chain(self.vertical.iter())
+    }
+
+    fn is_empty(&self) -> bool {
+        self.horizontal.is_empty() && self.vertical.is_empty()
+    }
+
+    fn summarize(&self) -> usize {
+        self.iter()
+            .map(|reflection| match reflection.axis {
+                Axis::Horizontal => reflection.index * 100,
+                Axis::Vertical => reflection.index,
+            })
+            .sum()
+    }
 }
 
 struct Pattern {
     graph: Vec<Vec<char>>,
+    rows: Vec<u64>,
+    columns: Vec<u64>,
 }
 
 impl From<&String> for Pattern {
     fn from(value: &String) -> Self {
         let mut graph = Vec::new();
-        for line in value.lines() {
+        let mut rows = Vec::new();
+        let mut columns = Vec::new();
+        let height = value.lines().count();
+        let width = value.lines().map(str::len).max().unwrap_or(0);
+        assert!(width <= 64 && height <= 64, "pattern too large");
+        for (y, line) in value.lines().enumerate() {
+            let mut row = 0;
+            columns.resize(line.len(), 0);
+            for (x, c) in line.chars().enumerate() {
+                if c == '#' {
+                    row |= 1 << x;
+                    columns[x] |= 1 << y;
+                }
+            }
+            rows.push(row);
             graph.push(line.chars().collect());
         }
-        Pattern { graph }
+        Pattern {
+            graph,
+            rows,
+            columns,
+        }
     }
 }
 
-impl Pattern {
-    fn find_reflection_horizontal(&self, skip: Option<Reflection>) -> Result<Reflection, String> {
-        let axis = Axis::Horizontal;
-        for i in 1..self.graph.len() {
-            if self.graph[i] == self.graph[i - 1] {
-                let mut reflection = true;
-                let mut forward = i + 1;
-                let mut backward = i as isize - 2;
-                while forward < self.graph.len() && backward >= 0 {
-                    if self.graph[forward] != self.graph[backward as usize] {
-                        reflection = false;
-                        break;
-                    }
-                    forward += 1;
-                    backward -= 1;
-                }
-                if reflection {
-                    let ret = Reflection { axis, index: i };
-                    if let Some(s) = skip {
-                        if !(s == ret) {
-                            return Ok(ret);
-                        }
-                    } else {
-                        return Ok(ret);
-                    }
-                }
+struct Mirror {
+    index: usize,
+    extent: usize,
+    /// Mismatched cells as `(line, bit)` on the side before the mirror.
+    cells: Vec<(usize, usize)>,
+}
+
+/// Every mirror in `lines` whose mirrored pairs differ in exactly `tolerance` cells.
+fn mirror_positions(lines: &[u64], tolerance: usize) -> Vec<Mirror> {
+    let mut retval = Vec::new();
+    for i in 1..lines.len() {
+        let mut mismatches = 0;
+        for (a, b) in lines[..i].iter().rev().zip(&lines[i..]) {
+            mismatches += (a ^ b).count_ones() as usize;
+            if mismatches > tolerance {
+                break;
             }
         }
-        Err("no reflection".to_string())
-    }
-
-    fn find_reflection_vertical(&self, skip: Option<Reflection>) -> Result<Reflection, String> {
-        let axis = Axis::Vertical;
-        for i in 1..self.graph[0].len() {
-            let a = self.graph.iter().map(|x| x[i]).collect::<Vec<char>>();
-            let b = self.graph.iter().map(|x| x[i - 1]).collect::<Vec<char>>();
-            if a == b {
-                let mut reflection = true;
-                let mut forward = i + 1;
-                let mut backward = i as isize - 2;
-                while forward < self.graph[0].len() && backward >= 0 {
-                    let a = self.graph.iter().map(|x| x[forward]).collect::<Vec<char>>();
-                    let b = self
-                        .graph
-                        .iter()
-                        .map(|x| x[backward as usize])
-                        .collect::<Vec<char>>();
-                    if a != b {
-                        reflection = false;
-                        break;
-                    }
-                    forward += 1;
-                    backward -= 1;
-                }
-                if reflection {
-                    let ret = Reflection { axis, index: i };
-                    if let Some(s) = skip {
-                        if !(s == ret) {
-                            return Ok(ret);
-                        }
-                    } else {
-                        return Ok(ret);
-                    }
+        if mismatches == tolerance {
+            let extent = i.min(lines.len() - i);
+            let mut cells = Vec::new();
+            for offset in 0..extent {
+                let line = i - 1 - offset;
+                let mut diff = lines[line] ^ lines[i + offset];
+                while diff != 0 {
+                    cells.push((line, diff.trailing_zeros() as usize));
+                    diff &= diff - 1;
                 }
             }
+            retval.push(Mirror {
+                index: i,
+                extent,
+                cells,
+            });
         }
-        Err("no reflection".to_string())
     }
+    retval
+}
 
-    fn find_reflection(&self) -> Reflection {
-        match self.find_reflection_horizontal(None) {
-            Ok(reflection) => reflection,
-            Err(_) => self.find_reflection_vertical(None).unwrap(),
+impl Pattern {
+    fn find_reflections(&self, tolerance: usize) -> Reflections {
+        let horizontal = mirror_positions(&self.rows, tolerance)
+            .into_iter()
+            .map(|mirror| Reflection {
+                axis: Axis::Horizontal,
+                index: mirror.index,
+                extent: mirror.extent,
+                smudges: mirror.cells.into_iter().map(|(y, x)| (x, y)).collect(),
+            })
+            .collect();
+        let vertical = mirror_positions(&self.columns, tolerance)
+            .into_iter()
+            .map(|mirror| Reflection {
+                axis: Axis::Vertical,
+                index: mirror.index,
+                extent: mirror.extent,
+                smudges: mirror.cells,
+            })
+            .collect();
+        Reflections {
+            horizontal,
+            vertical,
         }
     }
 
-    fn smudges(&self) -> Vec<Pattern> {
-        let mut patterns = Vec::new();
-        let points = (0..self.graph.len()).cartesian_product(0..self.graph[0].len());
-        for (y, x) in points {
-            let mut graph = self.graph.clone();
-            match self.graph[y][x] {
-                '#' => graph[y][x] = '.',
-                '.' => graph[y][x] = '#',
-                _ => panic!("at the disco"),
+    /// Draws the pattern with the mirror line as `-` or `|` and any smudged cell as `*`.
+    fn render(&self, reflection: Option<&Reflection>) -> String {
+        let mut retval = String::new();
+        for (y, row) in self.graph.iter().enumerate() {
+            if reflection.is_some_and(|r| r.axis == Axis::Horizontal && r.index == y) {
+                retval.push_str(&"-".repeat(row.len()));
+                retval.push('\n');
+            }
+            for (x, &c) in row.iter().enumerate() {
+                if reflection.is_some_and(|r| r.axis == Axis::Vertical && r.index == x) {
+                    retval.push('|');
+                }
+                if reflection.is_some_and(|r| r.smudges.contains(&(x, y))) {
+                    retval.push('*');
+                } else {
+                    retval.push(c);
+                }
             }
-            patterns.push(Pattern { graph });
+            retval.push('\n');
         }
-        patterns
+        retval
     }
 }
 
-fn part1(patterns: &Vec<Pattern>) {
+fn describe(pattern: &Pattern, reflections: &Reflections) {
+    if reflections.is_empty() {
+        println!("no reflection");
+        print!("{}", pattern.render(None));
+    }
+    for reflection in reflections.iter() {
+        println!(
+            "{:?} reflection at {} spanning {} on each side, smudges {:?}",
+            reflection.axis, reflection.index, reflection.extent, reflection.smudges
+        );
+        print!("{}", pattern.render(Some(reflection)));
+    }
+}
+
+fn part1(patterns: &[Pattern]) -> usize {
     let mut sum = 0;
     for pattern in patterns.iter() {
-        let reflection = pattern.find_reflection();
-        match reflection.axis {
-            Axis::Horizontal => sum += reflection.index * 100,
-            Axis::Vertical => sum += reflection.index,
-        }
+        sum += pattern.find_reflections(0).summarize();
     }
-    println!("part1: {}", sum)
+    sum
 }
 
-fn part2(patterns: &Vec<Pattern>) {
+fn part2(patterns: &[Pattern]) -> usize {
     let mut sum = 0;
     for (idx, item) in patterns.iter().enumerate() {
-        let original = item.find_reflection();
-        let mut reflections = HashSet::new();
-        for pattern in item.smudges() {
-            if let Ok(reflection) = pattern.find_reflection_horizontal(Some(original)) {
-                reflections.insert(reflection);
-            }
-            if let Ok(reflection) = pattern.find_reflection_vertical(Some(original)) {
-                reflections.insert(reflection);
-            }
-        }
-        for reflection in &reflections {
-            match reflection.axis {
-                Axis::Horizontal => sum += reflection.index * 100,
-                Axis::Vertical => sum += reflection.index,
-            }
-        }
-        if reflections.len() == 0 {
-            println!("{idx}")
+        let reflections = item.find_reflections(1);
+        sum += reflections.summarize();
+        if reflections.is_empty() {
+            println!("pattern {} has no smudged reflection:", idx + 1);
+            print!("{}", item.render(None));
         }
     }
-    println!("part2: {}", sum)
+    sum
 }
 
-fn main() {
-    let input = fs::read_to_string("input.txt").expect("input.txt should exist");
+/// Splits the input on blank lines into patterns.
+fn read_patterns(input: &str) -> Vec<Pattern> {
     let mut buf = String::new();
     let mut patterns = Vec::new();
     for line in input.lines() {
@@ -173,6 +213,52 @@ fn main() {
         }
     }
     patterns.push(Pattern::from(&buf));
-    part1(&patterns);
-    part2(&patterns);
+    patterns
+}
+
+fn main() {
+    let input = fs::read_to_string("input.txt").expect("input.txt should exist");
+    let patterns = read_patterns(&input);
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    if args.first().map(String::as_str) == Some("show") {
+        let pattern = args
+            .get(1)
+            .and_then(|n| n.parse::<usize>().ok())
+            .and_then(|n| n.checked_sub(1))
+            .and_then(|idx| patterns.get(idx));
+        let Some(pattern) = pattern else {
+            println!("usage: show <pattern, from 1> [tolerance]");
+            return;
+        };
+        let tolerance = args
+            .get(2)
+            .map_or(0, |t| t.parse().expect("tolerance should be a number"));
+        describe(pattern, &pattern.find_reflections(tolerance));
+        return;
+    }
+    println!("part1: {}", part1(&patterns));
+    println!("part2: {}", part2(&patterns));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_p1() {
+        let input = fs::read_to_string("test.txt").expect("test.txt should exist");
+        assert_eq!(405, part1(&read_patterns(&input)));
+    }
+
+    #[test]
+    fn test_p2() {
+        let input = fs::read_to_string("test.txt").expect("test.txt should exist");
+        assert_eq!(400, part2(&read_patterns(&input)));
+    }
+
+    #[test]
+    #[should_panic(expected = "pattern too large")]
+    fn test_pattern_too_large() {
+        let _ = Pattern::from(&format!("{}\n", "#".repeat(65)));
+    }
 }
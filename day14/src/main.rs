@@ -1,149 +1,260 @@
-use std::fs;
-
-fn load(grid: &Vec<Vec<char>>) -> usize {
-    let mut sum = 0;
-    let reversed = grid.clone().into_iter().rev().collect::<Vec<Vec<char>>>();
-    for (idx, row) in reversed.into_iter().enumerate() {
-        for rock in row {
-            if rock == 'O' {
-                sum += idx + 1
+use std::collections::HashMap;
+use std::fmt;
+use std::{env, fs};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+enum Direction {
+    North,
+    West,
+    South,
+    East,
+}
+
+/// A run of open cells between cube rocks, as `(first index, length)` along a line.
+type Segment = (u32, u32);
+
+/// The reflector dish, with each column stored as a bitset indexed by row.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+struct Platform {
+    width: usize,
+    height: usize,
+    rocks: Vec<u128>,
+    cubes: Vec<u128>,
+    column_segments: Vec<Vec<Segment>>,
+    row_segments: Vec<Vec<Segment>>,
+}
+
+fn segments(len: usize, blocked: impl Fn(usize) -> bool) -> Vec<Segment> {
+    let mut retval = Vec::new();
+    let mut start = 0;
+    for idx in 0..=len {
+        if idx == len || blocked(idx) {
+            if idx > start {
+                retval.push((start as u32, (idx - start) as u32));
             }
+            start = idx + 1;
         }
     }
-    sum
+    retval
 }
 
-fn tilt(grid: &Vec<Vec<char>>) -> Vec<Vec<char>> {
-    let mut retval = grid.clone();
-
-    for y in 0..retval.len() {
-        if y == 0 {
+/// Slides every rock in `line` to the low or high end of its segment.
+fn pack(line: u128, segments: &[Segment], towards_start: bool) -> u128 {
+    let mut retval = 0;
+    for &(start, len) in segments {
+        let mask = (u128::MAX >> (128 - len)) << start;
+        let count = (line & mask).count_ones();
+        if count == 0 {
             continue;
         }
-        for x in 0..retval[y].len() {
-            if retval[y][x] == 'O' {
-                let mut local = y as isize - 1;
-                while local >= 0 && retval[local as usize][x] == '.' {
-                    retval[local as usize][x] = 'O';
-                    retval[local as usize + 1][x] = '.';
-                    local -= 1;
-                }
-            }
-        }
+        let rocks = u128::MAX >> (128 - count);
+        retval |= if towards_start {
+            rocks << start
+        } else {
+            rocks << (start + len - count)
+        };
     }
-
     retval
 }
 
-fn spin_inner(grid: Vec<Vec<char>>) -> Vec<Vec<char>> {
-    let mut retval = grid.clone();
-
-    // North
-    for y in 0..retval.len() {
-        if y == 0 {
-            continue;
+/// Swaps between per-column and per-row bitsets.
+fn transpose(lines: &[u128], len: usize) -> Vec<u128> {
+    let mut retval = vec![0; len];
+    for (idx, &line) in lines.iter().enumerate() {
+        let mut bits = line;
+        while bits != 0 {
+            retval[bits.trailing_zeros() as usize] |= 1 << idx;
+            bits &= bits - 1;
         }
-        for x in 0..retval[y].len() {
-            if retval[y][x] == 'O' {
-                let mut local = y as isize - 1;
-                while local >= 0 && retval[local as usize][x] == '.' {
-                    retval[local as usize][x] = 'O';
-                    retval[local as usize + 1][x] = '.';
-                    local -= 1;
+    }
+    retval
+}
+
+impl From<&String> for Platform {
+    fn from(value: &String) -> Self {
+        let grid = value
+            .lines()
+            .map(|line| line.chars().collect())
+            .collect::<Vec<Vec<char>>>();
+        let height = grid.len();
+        let width = grid[0].len();
+        assert!(width <= 128 && height <= 128, "platform too large");
+        let mut rocks = vec![0; width];
+        let mut cubes = vec![0; width];
+        for (y, row) in grid.iter().enumerate() {
+            for (x, &c) in row.iter().enumerate() {
+                match c {
+                    'O' => rocks[x] |= 1 << y,
+                    '#' => cubes[x] |= 1 << y,
+                    _ => (),
                 }
             }
         }
+        let column_segments = cubes
+            .iter()
+            .map(|&column| segments(height, |y| column & (1 << y) != 0))
+            .collect();
+        let row_segments = transpose(&cubes, height)
+            .iter()
+            .map(|&row| segments(width, |x| row & (1 << x) != 0))
+            .collect();
+        Platform {
+            width,
+            height,
+            rocks,
+            cubes,
+            column_segments,
+            row_segments,
+        }
     }
+}
 
-    // West
-    for x in 0..retval[0].len() {
-        if x == 0 {
-            continue;
-        }
-        for y in 0..retval.len() {
-            if retval[y][x] == 'O' {
-                let mut local = x as isize - 1;
-                while local >= 0 && retval[y][local as usize] == '.' {
-                    retval[y][local as usize] = 'O';
-                    retval[y][local as usize + 1] = '.';
-                    local -= 1;
-                }
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let c = if self.rocks[x] & (1 << y) != 0 {
+                    'O'
+                } else if self.cubes[x] & (1 << y) != 0 {
+                    '#'
+                } else {
+                    '.'
+                };
+                write!(f, "{c}")?;
             }
+            writeln!(f)?;
         }
+        Ok(())
     }
+}
 
-    // South
-    for y in (0..retval.len()).rev() {
-        if y == (retval.len() - 1) {
-            continue;
-        }
-        for x in 0..retval[y].len() {
-            if retval[y][x] == 'O' {
-                let mut local = y + 1;
-                while local < retval.len() && retval[local][x] == '.' {
-                    retval[local][x] = 'O';
-                    retval[local - 1][x] = '.';
-                    local += 1;
+impl Platform {
+    fn tilt(&mut self, direction: Direction) {
+        match direction {
+            Direction::North | Direction::South => {
+                let towards_start = direction == Direction::North;
+                for (column, segments) in self.rocks.iter_mut().zip(&self.column_segments) {
+                    *column = pack(*column, segments, towards_start);
                 }
             }
+            Direction::West | Direction::East => {
+                let towards_start = direction == Direction::West;
+                let rows = transpose(&self.rocks, self.height)
+                    .into_iter()
+                    .zip(&self.row_segments)
+                    .map(|(row, segments)| pack(row, segments, towards_start))
+                    .collect::<Vec<u128>>();
+                self.rocks = transpose(&rows, self.width);
+            }
         }
     }
 
-    // East
-    for x in (0..retval[0].len()).rev() {
-        if x == (retval[0].len() - 1) {
-            continue;
+    fn spin(&mut self) {
+        for direction in [
+            Direction::North,
+            Direction::West,
+            Direction::South,
+            Direction::East,
+        ] {
+            self.tilt(direction);
         }
-        for y in 0..retval.len() {
-            if retval[y][x] == 'O' {
-                let mut local = x + 1;
-                while local < retval[y].len() && retval[y][local] == '.' {
-                    retval[y][local] = 'O';
-                    retval[y][local - 1] = '.';
-                    local += 1;
-                }
+    }
+
+    fn load(&self) -> usize {
+        let mut sum = 0;
+        for &column in &self.rocks {
+            let mut bits = column;
+            while bits != 0 {
+                sum += self.height - bits.trailing_zeros() as usize;
+                bits &= bits - 1;
             }
         }
+        sum
     }
+}
 
-    retval
+/// The loads seen while spinning a platform until its rock layout repeats.
+struct SpinCycle {
+    /// `loads[i]` is the north load after `i` spin cycles.
+    loads: Vec<usize>,
+    /// Number of cycles before the repeating part starts.
+    prefix: usize,
+    period: usize,
 }
 
-fn spin(grid: &Vec<Vec<char>>, cycles: usize) -> Vec<Vec<char>> {
-    let mut retval = grid.clone();
-    let mut states = Vec::new();
-    let mut cycle_start_index = 0;
-
-    for _ in 0..cycles {
-        states.push(retval.clone());
-        retval = spin_inner(retval);
-        if states.contains(&retval) {
-            cycle_start_index = states
-                .iter()
-                .position(|x| x.clone() == retval)
-                .expect("exists");
-            break;
+impl From<&Platform> for SpinCycle {
+    fn from(value: &Platform) -> Self {
+        let mut platform = value.clone();
+        let mut seen = HashMap::new();
+        let mut loads = Vec::new();
+        loop {
+            if let Some(&prefix) = seen.get(&platform.rocks) {
+                return SpinCycle {
+                    period: loads.len() - prefix,
+                    loads,
+                    prefix,
+                };
+            }
+            seen.insert(platform.rocks.clone(), loads.len());
+            loads.push(platform.load());
+            platform.spin();
         }
     }
+}
 
-    states[cycle_start_index..][(cycles - cycle_start_index) % (states.len() - cycle_start_index)]
-        .clone()
+impl SpinCycle {
+    fn load_after(&self, cycles: usize) -> usize {
+        if cycles < self.prefix {
+            self.loads[cycles]
+        } else {
+            self.loads[self.prefix + (cycles - self.prefix) % self.period]
+        }
+    }
 }
 
-fn part1(grid: &Vec<Vec<char>>) {
-    println!("part1: {}", load(&tilt(grid)))
+fn part1(platform: &Platform) -> usize {
+    let mut tilted = platform.clone();
+    tilted.tilt(Direction::North);
+    tilted.load()
 }
 
-fn part2(grid: &Vec<Vec<char>>) {
-    println!("part2: {}", load(&spin(grid, 1000000000)))
+fn part2(platform: &Platform) -> usize {
+    SpinCycle::from(platform).load_after(1000000000)
 }
 
 fn main() {
     let input = fs::read_to_string("input.txt").expect("input.txt should exist");
-    let mut grid = Vec::new();
-    for line in input.lines() {
-        grid.push(line.chars().collect());
+    let platform = Platform::from(&input);
+    if env::args().nth(1).as_deref() == Some("loads") {
+        let cycle = SpinCycle::from(&platform);
+        println!("prefix: {}, period: {}", cycle.prefix, cycle.period);
+        for (idx, load) in cycle.loads.iter().enumerate() {
+            let marker = if idx == cycle.prefix {
+                " <- cycle start"
+            } else {
+                ""
+            };
+            println!("{idx}: {load}{marker}");
+        }
+        return;
+    }
+    println!("part1: {}", part1(&platform));
+    println!("part2: {}", part2(&platform));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_p1() {
+        let input = fs::read_to_string("test.txt").expect("test.txt should exist");
+        assert_eq!(136, part1(&Platform::from(&input)));
+    }
+
+    #[test]
+    fn test_p2() {
+        let input = fs::read_to_string("test.txt").expect("test.txt should exist");
+        assert_eq!(64, part2(&Platform::from(&input)));
     }
-    part1(&grid);
-    part2(&grid);
 }
@@ -1,34 +1,35 @@
-use std::collections::HashMap;
-use std::fs;
+use std::fmt;
+use std::ops::Range;
+use std::{env, fs};
 
-#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
 enum Op {
     Remove,
     Add,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 struct Lens {
-    label: Vec<char>,
+    label: String,
     focal_length: usize,
 }
 
 struct Instruction {
-    code: Vec<char>,
+    code: String,
 }
 
 impl From<&str> for Instruction {
     fn from(value: &str) -> Self {
         Instruction {
-            code: value.chars().collect(),
+            code: value.to_string(),
         }
     }
 }
 
-fn HASH(input: &Vec<char>) -> usize {
+fn hash(input: &str) -> usize {
     let mut current_value = 0;
-    for c in input {
-        current_value += *c as usize;
+    for c in input.chars() {
+        current_value += c as usize;
         current_value *= 17;
         current_value %= 256;
     }
@@ -36,89 +37,268 @@ fn HASH(input: &Vec<char>) -> usize {
 }
 
 impl Instruction {
-    fn HASH(&self) -> usize {
-        HASH(&self.code)
-    }
-
-    fn HASHMAP(&self) -> (usize, Op, Lens) {
-        let b;
-        let op;
-        let lens;
-        if self.code.contains(&'=') {
-            let mut label = self.code.clone();
-            let focal_length = label.pop().unwrap().to_digit(10).unwrap() as usize;
-            label.pop();
-
-            b = HASH(&label);
-            op = Op::Add;
-            lens = Lens {
-                label,
-                focal_length,
-            }
+    fn hash(&self) -> usize {
+        hash(&self.code)
+    }
+
+    /// Splits a `label=N` or `label-` step into its operation and lens.
+    fn parse(&self) -> Result<(Op, Lens), String> {
+        let (label, op, focal_length) = if let Some((label, length)) = self.code.split_once('=') {
+            let focal_length = length
+                .parse::<usize>()
+                .map_err(|e| format!("bad focal length in {:?}: {e}", self.code))?;
+            (label, Op::Add, focal_length)
+        } else if let Some(label) = self.code.strip_suffix('-') {
+            (label, Op::Remove, 0)
         } else {
-            let mut label = self.code.clone();
-            label.pop();
-            b = HASH(&label);
-            op = Op::Remove;
-            lens = Lens {
-                label,
-                focal_length: 0,
-            }
+            return Err(format!("unknown operation in {:?}", self.code));
+        };
+        if label.is_empty() || !label.chars().all(|c| c.is_ascii_alphabetic()) {
+            return Err(format!("bad label in {:?}", self.code));
         }
-        (b, op, lens)
+        Ok((
+            op,
+            Lens {
+                label: label.to_string(),
+                focal_length,
+            },
+        ))
     }
 }
 
-fn part1(instructions: &Vec<Instruction>) {
-    let mut sum = 0;
-    for inst in instructions {
-        sum += inst.HASH();
-    }
-    println!("part1: {}", sum)
+/// The 256 boxes of the HASHMAP procedure, each keeping its lenses in insertion order.
+struct LensLibrary {
+    boxes: Vec<Vec<Lens>>,
 }
 
-fn part2(instructions: &Vec<Instruction>) {
-    let mut boxes: HashMap<usize, Vec<Lens>> = HashMap::new();
-    for inst in instructions {
-        let (key, op, lens) = inst.HASHMAP();
+impl LensLibrary {
+    fn new() -> Self {
+        LensLibrary {
+            boxes: vec![Vec::new(); 256],
+        }
+    }
+
+    /// Replaces the focal length of a lens already in its box, otherwise adds it at the back.
+    fn insert(&mut self, label: &str, focal_length: usize) {
+        let lenses = &mut self.boxes[hash(label)];
+        if let Some(lens) = lenses.iter_mut().find(|l| l.label == label) {
+            lens.focal_length = focal_length;
+        } else {
+            lenses.push(Lens {
+                label: label.to_string(),
+                focal_length,
+            });
+        }
+    }
+
+    fn remove(&mut self, label: &str) -> Option<Lens> {
+        let lenses = &mut self.boxes[hash(label)];
+        let index = lenses.iter().position(|l| l.label == label)?;
+        Some(lenses.remove(index))
+    }
+
+    fn get(&self, label: &str) -> Option<&Lens> {
+        self.boxes[hash(label)].iter().find(|l| l.label == label)
+    }
+
+    /// Every lens as `(box, slot, lens)`, box by box and in insertion order within each box.
+    fn iter(&self) -> impl Iterator<Item = (usize, usize, &Lens)> {
+        self.boxes.iter().enumerate().flat_map(|(key, lenses)| {
+            lenses
+                .iter()
+                .enumerate()
+                .map(move |(slot, lens)| (key, slot, lens))
+        })
+    }
+
+    fn apply(&mut self, instruction: &Instruction) -> Result<(), String> {
+        let (op, lens) = instruction.parse()?;
         match op {
+            Op::Add => self.insert(&lens.label, lens.focal_length),
             Op::Remove => {
-                if let Some(lenses) = boxes.get_mut(&key) {
-                    if let Some(index) = lenses.iter().position(|l| l.label == lens.label) {
-                        lenses.remove(index);
-                    }
-                }
+                self.remove(&lens.label);
+            }
+        }
+        Ok(())
+    }
+
+    fn focusing_power(&self) -> usize {
+        self.iter()
+            .map(|(key, slot, lens)| (key + 1) * (slot + 1) * lens.focal_length)
+            .sum()
+    }
+}
+
+impl fmt::Display for LensLibrary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (key, lenses) in self.boxes.iter().enumerate() {
+            if lenses.is_empty() {
+                continue;
             }
-            Op::Add => {
-                if let Some(lenses) = boxes.get_mut(&key) {
-                    if let Some(index) = lenses.iter().position(|l| l.label == lens.label) {
-                        lenses[index] = lens;
-                    } else {
-                        lenses.push(lens);
-                    }
-                } else {
-                    let mut lenses = Vec::new();
-                    lenses.push(lens);
-                    boxes.insert(key, lenses);
-                }
+            write!(f, "Box {key}:")?;
+            for lens in lenses {
+                write!(f, " [{} {}]", lens.label, lens.focal_length)?;
             }
+            writeln!(f)?;
         }
+        Ok(())
     }
-    let mut focusing_power = 0;
-    for (key, value) in boxes.iter() {
-        for (index, lens) in value.iter().enumerate() {
-            focusing_power += (*key + 1) * (index + 1) * lens.focal_length;
+}
+
+/// Runs every instruction, recording the non-empty boxes after each one in `steps`.
+fn trace(instructions: &[Instruction], steps: Range<usize>) -> Result<String, String> {
+    let mut library = LensLibrary::new();
+    let mut retval = String::new();
+    for (idx, inst) in instructions.iter().enumerate() {
+        library.apply(inst)?;
+        if steps.contains(&idx) {
+            retval.push_str(&format!("After {:?}:\n{library}\n", inst.code));
         }
     }
-    println!("part2: {}", focusing_power)
+    Ok(retval)
+}
+
+fn part1(instructions: &[Instruction]) {
+    let mut sum = 0;
+    for inst in instructions {
+        sum += inst.hash();
+    }
+    println!("part1: {}", sum)
+}
+
+fn part2(instructions: &[Instruction]) {
+    let mut library = LensLibrary::new();
+    for inst in instructions {
+        library.apply(inst).expect("instruction should be valid");
+    }
+    println!("part2: {}", library.focusing_power())
 }
 
 fn main() {
     let input = fs::read_to_string("input.txt").expect("input.txt should exist");
     let mut instructions = Vec::new();
-    for item in input.split(",") {
+    for item in input.trim().split(',') {
         instructions.push(Instruction::from(item));
     }
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    match args.first().map(String::as_str) {
+        Some("lens") => {
+            let Some(label) = args.get(1) else {
+                println!("usage: lens <label>");
+                return;
+            };
+            let mut library = LensLibrary::new();
+            for inst in &instructions {
+                library.apply(inst).expect("instruction should be valid");
+            }
+            match library.get(label) {
+                Some(lens) => println!("box {}: [{} {}]", hash(label), label, lens.focal_length),
+                None => println!("{label} is not in box {}", hash(label)),
+            }
+            return;
+        }
+        Some("trace") => {
+            let from = args
+                .get(1)
+                .map_or(1, |v| v.parse().expect("step should be a number"));
+            let to = args.get(2).map_or(instructions.len(), |v| {
+                v.parse().expect("step should be a number")
+            });
+            match trace(&instructions, from.max(1) - 1..to) {
+                Ok(steps) => print!("{steps}"),
+                Err(e) => println!("{e}"),
+            }
+            return;
+        }
+        _ => (),
+    }
     part1(&instructions);
     part2(&instructions);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "rn=1,cm-,qp=3,cm=2,qp-,pc=4,ot=9,ab=5,pc-,pc=6,ot=7\n";
+
+    #[test]
+    fn test_focusing_power() {
+        let mut library = LensLibrary::new();
+        for item in EXAMPLE.trim().split(',') {
+            library.apply(&Instruction::from(item)).unwrap();
+        }
+        assert_eq!(145, library.focusing_power());
+    }
+
+    #[test]
+    fn test_trace() {
+        let instructions = EXAMPLE
+            .trim()
+            .split(',')
+            .map(Instruction::from)
+            .collect::<Vec<Instruction>>();
+        let expected = [
+            "After \"rn=1\":",
+            "Box 0: [rn 1]",
+            "",
+            "After \"cm-\":",
+            "Box 0: [rn 1]",
+            "",
+            "After \"qp=3\":",
+            "Box 0: [rn 1]",
+            "Box 1: [qp 3]",
+            "",
+            "After \"cm=2\":",
+            "Box 0: [rn 1] [cm 2]",
+            "Box 1: [qp 3]",
+            "",
+            "After \"qp-\":",
+            "Box 0: [rn 1] [cm 2]",
+            "",
+            "After \"pc=4\":",
+            "Box 0: [rn 1] [cm 2]",
+            "Box 3: [pc 4]",
+            "",
+            "After \"ot=9\":",
+            "Box 0: [rn 1] [cm 2]",
+            "Box 3: [pc 4] [ot 9]",
+            "",
+            "After \"ab=5\":",
+            "Box 0: [rn 1] [cm 2]",
+            "Box 3: [pc 4] [ot 9] [ab 5]",
+            "",
+            "After \"pc-\":",
+            "Box 0: [rn 1] [cm 2]",
+            "Box 3: [ot 9] [ab 5]",
+            "",
+            "After \"pc=6\":",
+            "Box 0: [rn 1] [cm 2]",
+            "Box 3: [ot 9] [ab 5] [pc 6]",
+            "",
+            "After \"ot=7\":",
+            "Box 0: [rn 1] [cm 2]",
+            "Box 3: [ot 7] [ab 5] [pc 6]",
+            "",
+        ];
+        let actual = trace(&instructions, 0..instructions.len()).unwrap();
+        for (expected, actual) in expected.iter().zip(actual.lines()) {
+            assert_eq!(*expected, actual);
+        }
+        assert_eq!(expected.len(), actual.lines().count());
+
+        let partial = trace(&instructions, 3..5).unwrap();
+        assert_eq!(expected[10..17].join("\n") + "\n", partial);
+    }
+
+    #[test]
+    fn test_multi_digit_focal_length() {
+        let mut library = LensLibrary::new();
+        library.apply(&Instruction::from("rn=12")).unwrap();
+        assert_eq!(12, library.get("rn").unwrap().focal_length);
+        library.apply(&Instruction::from("rn-")).unwrap();
+        assert_eq!(None, library.get("rn"));
+        assert!(Instruction::from("rn=").parse().is_err());
+        assert!(Instruction::from("rn").parse().is_err());
+    }
+}
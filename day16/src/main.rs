@@ -1,5 +1,8 @@
-use std::collections::HashSet;
-use std::fs;
+use std::cmp::Reverse;
+use std::io::Write;
+use std::{env, fs, io};
+
+use rayon::prelude::*;
 
 use crate::Direction::*;
 use crate::Space::*;
@@ -26,6 +29,44 @@ impl From<char> for Space {
     }
 }
 
+impl From<Space> for char {
+    fn from(value: Space) -> Self {
+        match value {
+            Empty => '.',
+            RightMirror => '/',
+            LeftMirror => '\\',
+            HorizontalSplitter => '-',
+            VerticalSplitter => '|',
+        }
+    }
+}
+
+impl Space {
+    /// The directions a beam leaves this space in after entering it heading `direction`.
+    fn transitions(self, direction: Direction) -> &'static [Direction] {
+        match (self, direction) {
+            (Empty, Up) => &[Up],
+            (Empty, Down) => &[Down],
+            (Empty, Left) => &[Left],
+            (Empty, Right) => &[Right],
+            (RightMirror, Up) => &[Right],
+            (RightMirror, Down) => &[Left],
+            (RightMirror, Left) => &[Down],
+            (RightMirror, Right) => &[Up],
+            (LeftMirror, Up) => &[Left],
+            (LeftMirror, Down) => &[Right],
+            (LeftMirror, Left) => &[Up],
+            (LeftMirror, Right) => &[Down],
+            (HorizontalSplitter, Up | Down) => &[Left, Right],
+            (HorizontalSplitter, Left) => &[Left],
+            (HorizontalSplitter, Right) => &[Right],
+            (VerticalSplitter, Left | Right) => &[Up, Down],
+            (VerticalSplitter, Up) => &[Up],
+            (VerticalSplitter, Down) => &[Down],
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
 enum Direction {
     Up,
@@ -34,6 +75,26 @@ enum Direction {
     Right,
 }
 
+impl Direction {
+    fn bit(self) -> u8 {
+        match self {
+            Up => 1,
+            Down => 2,
+            Left => 4,
+            Right => 8,
+        }
+    }
+}
+
+/// A beam entering the contraption from its edge, and how many tiles it energizes.
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
+struct Launch {
+    x: usize,
+    y: usize,
+    direction: Direction,
+    energized: usize,
+}
+
 #[derive(Clone, Debug, Hash, Eq, PartialEq)]
 struct Contraption {
     spaces: Vec<Vec<Space>>,
@@ -87,195 +148,32 @@ impl Contraption {
         }
     }
 
-    fn travel(
-        &self,
-        x: usize,
-        y: usize,
-        direction: Direction,
-        visited: &mut HashSet<(usize, usize, Direction)>,
-    ) {
-        visited.insert((x, y, direction));
-        let current_space = self.spaces[y][x];
-        match current_space {
-            Empty => {
-                if let Some((next_x, next_y)) = self.next(x, y, direction) {
-                    if visited.contains(&(next_x, next_y, direction)) {
-                        return;
-                    }
-                    self.travel(next_x, next_y, direction, visited)
-                }
+    /// Follows the beam from `(x, y)` with an explicit worklist, returning for every cell a
+    /// bitmask of the directions beams have passed through it in.
+    fn energize(&self, x: usize, y: usize, direction: Direction) -> Vec<u8> {
+        let width = self.spaces[0].len();
+        let mut visited = vec![0u8; width * self.spaces.len()];
+        let mut beams = vec![(x, y, direction)];
+        while let Some((x, y, direction)) = beams.pop() {
+            let cell = &mut visited[y * width + x];
+            if *cell & direction.bit() != 0 {
+                continue;
             }
-            RightMirror => match direction {
-                Up => {
-                    if let Some((next_x, next_y)) = self.next(x, y, Right) {
-                        if visited.contains(&(next_x, next_y, Right)) {
-                            return;
-                        }
-                        self.travel(next_x, next_y, Right, visited)
-                    }
+            *cell |= direction.bit();
+            for &next_direction in self.spaces[y][x].transitions(direction) {
+                if let Some((next_x, next_y)) = self.next(x, y, next_direction) {
+                    beams.push((next_x, next_y, next_direction));
                 }
-                Down => {
-                    if let Some((next_x, next_y)) = self.next(x, y, Left) {
-                        if visited.contains(&(next_x, next_y, Left)) {
-                            return;
-                        }
-                        self.travel(next_x, next_y, Left, visited)
-                    }
-                }
-                Left => {
-                    if let Some((next_x, next_y)) = self.next(x, y, Down) {
-                        if visited.contains(&(next_x, next_y, Down)) {
-                            return;
-                        }
-                        self.travel(next_x, next_y, Down, visited)
-                    }
-                }
-                Right => {
-                    if let Some((next_x, next_y)) = self.next(x, y, Up) {
-                        if visited.contains(&(next_x, next_y, Up)) {
-                            return;
-                        }
-                        self.travel(next_x, next_y, Up, visited)
-                    }
-                }
-            },
-            LeftMirror => match direction {
-                Up => {
-                    if let Some((next_x, next_y)) = self.next(x, y, Left) {
-                        if visited.contains(&(next_x, next_y, Left)) {
-                            return;
-                        }
-                        self.travel(next_x, next_y, Left, visited)
-                    }
-                }
-                Down => {
-                    if let Some((next_x, next_y)) = self.next(x, y, Right) {
-                        if visited.contains(&(next_x, next_y, Right)) {
-                            return;
-                        }
-                        self.travel(next_x, next_y, Right, visited)
-                    }
-                }
-                Left => {
-                    if let Some((next_x, next_y)) = self.next(x, y, Up) {
-                        if visited.contains(&(next_x, next_y, Up)) {
-                            return;
-                        }
-                        self.travel(next_x, next_y, Up, visited)
-                    }
-                }
-                Right => {
-                    if let Some((next_x, next_y)) = self.next(x, y, Down) {
-                        if visited.contains(&(next_x, next_y, Down)) {
-                            return;
-                        }
-                        self.travel(next_x, next_y, Down, visited)
-                    }
-                }
-            },
-            HorizontalSplitter => match direction {
-                Up => {
-                    if let Some((next_x, next_y)) = self.next(x, y, Right) {
-                        if visited.contains(&(next_x, next_y, Right)) {
-                            return;
-                        }
-                        self.travel(next_x, next_y, Right, visited)
-                    }
-                    if let Some((next_x, next_y)) = self.next(x, y, Left) {
-                        if visited.contains(&(next_x, next_y, Left)) {
-                            return;
-                        }
-                        self.travel(next_x, next_y, Left, visited)
-                    }
-                }
-                Down => {
-                    if let Some((next_x, next_y)) = self.next(x, y, Right) {
-                        if visited.contains(&(next_x, next_y, Right)) {
-                            return;
-                        }
-                        self.travel(next_x, next_y, Right, visited)
-                    }
-                    if let Some((next_x, next_y)) = self.next(x, y, Left) {
-                        if visited.contains(&(next_x, next_y, Left)) {
-                            return;
-                        }
-                        self.travel(next_x, next_y, Left, visited)
-                    }
-                }
-                Left => {
-                    if let Some((next_x, next_y)) = self.next(x, y, direction) {
-                        if visited.contains(&(next_x, next_y, direction)) {
-                            return;
-                        }
-                        self.travel(next_x, next_y, direction, visited)
-                    }
-                }
-                Right => {
-                    if let Some((next_x, next_y)) = self.next(x, y, direction) {
-                        if visited.contains(&(next_x, next_y, direction)) {
-                            return;
-                        }
-                        self.travel(next_x, next_y, direction, visited)
-                    }
-                }
-            },
-            VerticalSplitter => match direction {
-                Up => {
-                    if let Some((next_x, next_y)) = self.next(x, y, direction) {
-                        if visited.contains(&(next_x, next_y, direction)) {
-                            return;
-                        }
-                        self.travel(next_x, next_y, direction, visited)
-                    }
-                }
-                Down => {
-                    if let Some((next_x, next_y)) = self.next(x, y, direction) {
-                        if visited.contains(&(next_x, next_y, direction)) {
-                            return;
-                        }
-                        self.travel(next_x, next_y, direction, visited)
-                    }
-                }
-                Left => {
-                    if let Some((next_x, next_y)) = self.next(x, y, Up) {
-                        if visited.contains(&(next_x, next_y, Up)) {
-                            return;
-                        }
-                        self.travel(next_x, next_y, Up, visited)
-                    }
-                    if let Some((next_x, next_y)) = self.next(x, y, Down) {
-                        if visited.contains(&(next_x, next_y, Down)) {
-                            return;
-                        }
-                        self.travel(next_x, next_y, Down, visited)
-                    }
-                }
-                Right => {
-                    if let Some((next_x, next_y)) = self.next(x, y, Up) {
-                        if visited.contains(&(next_x, next_y, Up)) {
-                            return;
-                        }
-                        self.travel(next_x, next_y, Up, visited)
-                    }
-                    if let Some((next_x, next_y)) = self.next(x, y, Down) {
-                        if visited.contains(&(next_x, next_y, Down)) {
-                            return;
-                        }
-                        self.travel(next_x, next_y, Down, visited)
-                    }
-                }
-            },
+            }
         }
+        visited
     }
 
     fn count_energized_points(&self, x: usize, y: usize, direction: Direction) -> usize {
-        let mut visited = HashSet::new();
-        self.travel(x, y, direction, &mut visited);
-        let mut energized = HashSet::new();
-        for point in visited.into_iter().map(|(x, y, _)| (x, y)) {
-            energized.insert(point);
-        }
-        energized.len()
+        self.energize(x, y, direction)
+            .into_iter()
+            .filter(|&cell| cell != 0)
+            .count()
     }
 
     fn direction(&self, x: usize, y: usize) -> Vec<Direction> {
@@ -294,36 +192,192 @@ impl Contraption {
         }
         retval
     }
-}
 
-fn part1(contraption: &Contraption) -> usize {
-    contraption.count_energized_points(0, 0, Right)
-}
+    /// Every edge tile paired with each direction that points into the contraption.
+    fn launches(&self) -> Vec<(usize, usize, Direction)> {
+        let mut retval = Vec::new();
+        for y in 0..self.spaces.len() {
+            for x in 0..self.spaces[y].len() {
+                for dir in self.direction(x, y) {
+                    retval.push((x, y, dir));
+                }
+            }
+        }
+        retval
+    }
 
-fn part2(contraption: &Contraption) -> usize {
-    let mut configurations = Vec::new();
-    let mut points = HashSet::new();
-    for y in 0..contraption.spaces.len() {
-        points.insert((0, y));
-        points.insert((contraption.spaces[y].len() - 1, y));
+    fn best_launch(&self) -> Launch {
+        self.launches()
+            .into_par_iter()
+            .map(|(x, y, direction)| Launch {
+                x,
+                y,
+                direction,
+                energized: self.count_energized_points(x, y, direction),
+            })
+            .max_by_key(|launch| (launch.energized, Reverse((launch.y, launch.x))))
+            .unwrap()
     }
-    for x in 0..contraption.spaces[0].len() {
-        points.insert((x, 0));
-        points.insert((x, contraption.spaces.len() - 1));
+
+    /// How many of the edge launches energize each cell, indexed `y * width + x`.
+    fn heatmap(&self) -> Vec<usize> {
+        let cells = self.spaces.len() * self.spaces[0].len();
+        self.launches()
+            .into_par_iter()
+            .map(|(x, y, direction)| self.energize(x, y, direction))
+            .fold(
+                || vec![0; cells],
+                |mut heat, visited| {
+                    for (count, cell) in heat.iter_mut().zip(visited) {
+                        *count += (cell != 0) as usize;
+                    }
+                    heat
+                },
+            )
+            .reduce(
+                || vec![0; cells],
+                |mut a, b| {
+                    a.iter_mut().zip(b).for_each(|(a, b)| *a += b);
+                    a
+                },
+            )
+    }
+
+    /// The energized tiles drawn as `#` like the puzzle does.
+    fn render_energized(&self, visited: &[u8]) -> String {
+        let width = self.spaces[0].len();
+        let mut retval = String::new();
+        for y in 0..self.spaces.len() {
+            for x in 0..width {
+                retval.push(if visited[y * width + x] != 0 {
+                    '#'
+                } else {
+                    '.'
+                });
+            }
+            retval.push('\n');
+        }
+        retval
+    }
+
+    /// The contraption with each tile's background coloured by `colours`.
+    fn render_ansi(&self, colours: &[(u8, u8, u8)]) -> String {
+        let width = self.spaces[0].len();
+        let mut retval = String::new();
+        for (y, row) in self.spaces.iter().enumerate() {
+            for (x, &space) in row.iter().enumerate() {
+                let (r, g, b) = colours[y * width + x];
+                retval.push_str(&format!("\x1b[48;2;{r};{g};{b}m{}", char::from(space)));
+            }
+            retval.push_str("\x1b[0m\n");
+        }
+        retval
     }
-    for (x, y) in points {
-        for dir in contraption.direction(x, y) {
-            configurations.push(contraption.count_energized_points(x, y, dir))
+
+    fn write_ppm(&self, out: &mut impl Write, colours: &[(u8, u8, u8)]) -> io::Result<()> {
+        write!(
+            out,
+            "P6\n{} {}\n255\n",
+            self.spaces[0].len(),
+            self.spaces.len()
+        )?;
+        for &(r, g, b) in colours {
+            out.write_all(&[r, g, b])?;
         }
+        Ok(())
+    }
+
+    fn write_png(&self, out: impl Write, colours: &[(u8, u8, u8)]) -> io::Result<()> {
+        let (width, height) = (self.spaces[0].len(), self.spaces.len());
+        let mut encoder = png::Encoder::new(out, width as u32, height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let data = colours
+            .iter()
+            .flat_map(|&(r, g, b)| [r, g, b])
+            .collect::<Vec<u8>>();
+        encoder.write_header()?.write_image_data(&data)?;
+        Ok(())
     }
-    configurations.into_iter().max().unwrap()
+}
+
+/// Black through red and yellow to white as `value` approaches `max`.
+fn heat_colour(value: usize, max: usize) -> (u8, u8, u8) {
+    let scaled = (value * 765 / max.max(1)) as u32;
+    (
+        scaled.min(255) as u8,
+        scaled.saturating_sub(255).min(255) as u8,
+        scaled.saturating_sub(510).min(255) as u8,
+    )
+}
+
+fn energized_colour(space: Space, energized: bool) -> (u8, u8, u8) {
+    match (energized, space) {
+        (true, _) => (255, 200, 0),
+        (false, Empty) => (0, 0, 0),
+        (false, _) => (96, 96, 96),
+    }
+}
+
+fn part1(contraption: &Contraption) -> usize {
+    contraption.count_energized_points(0, 0, Right)
+}
+
+fn part2(contraption: &Contraption) -> Launch {
+    contraption.best_launch()
 }
 
 fn main() {
     let input = fs::read_to_string("input.txt").expect("input.txt should exist");
     let contraption = Contraption::from(&input);
-    println!("part1: {}", part1(&contraption));
-    println!("part2: {}", part2(&contraption));
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    let colours = match args.first().map(String::as_str) {
+        Some("render") => {
+            let visited = contraption.energize(0, 0, Right);
+            if args.len() == 1 {
+                print!("{}", contraption.render_energized(&visited));
+                return;
+            }
+            contraption
+                .spaces
+                .iter()
+                .flatten()
+                .zip(&visited)
+                .map(|(&space, &cell)| energized_colour(space, cell != 0))
+                .collect::<Vec<(u8, u8, u8)>>()
+        }
+        Some("heatmap") => {
+            let heat = contraption.heatmap();
+            let max = heat.iter().copied().max().unwrap_or(0);
+            heat.iter().map(|&value| heat_colour(value, max)).collect()
+        }
+        _ => {
+            println!("part1: {}", part1(&contraption));
+            let best = part2(&contraption);
+            println!("part2: {}", best.energized);
+            println!(
+                "best launch: ({}, {}) heading {:?}",
+                best.x, best.y, best.direction
+            );
+            return;
+        }
+    };
+    match args.get(1).map(String::as_str) {
+        None | Some("ansi") => print!("{}", contraption.render_ansi(&colours)),
+        Some(path) if path.ends_with(".ppm") => {
+            let mut file = fs::File::create(path).expect("output should be writable");
+            contraption
+                .write_ppm(&mut file, &colours)
+                .expect("output should be writable");
+        }
+        Some(path) if path.ends_with(".png") => {
+            let file = fs::File::create(path).expect("output should be writable");
+            contraption
+                .write_png(io::BufWriter::new(file), &colours)
+                .expect("output should be writable");
+        }
+        Some(path) => println!("{path} should end in .ppm or .png"),
+    }
 }
 
 #[cfg(test)]
@@ -341,6 +395,26 @@ mod tests {
     fn test_p2() {
         let input = fs::read_to_string("test.txt").expect("test.txt should exist");
         let contraption = Contraption::from(&input);
-        assert_eq!(51, part2(&contraption));
+        assert_eq!(51, part2(&contraption).energized);
+    }
+
+    #[test]
+    fn test_render() {
+        let input = fs::read_to_string("test.txt").expect("test.txt should exist");
+        let contraption = Contraption::from(&input);
+        let expected = "\
+######....
+.#...#....
+.#...#####
+.#...##...
+.#...##...
+.#...##...
+.#..####..
+########..
+.#######..
+.#...#.#..
+";
+        let visited = contraption.energize(0, 0, Right);
+        assert_eq!(expected, contraption.render_energized(&visited));
     }
 }
@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// Shortest path with Dial's algorithm: a ring of `max_weight + 1` buckets stands in for the
+/// binary heap, which is cheaper when every edge costs a small integer. Takes the same
+/// arguments as `pathfinding::prelude::dijkstra` plus the largest weight `successors` returns.
+pub fn dial<N, FN, IN, FS>(
+    start: &N,
+    max_weight: usize,
+    mut successors: FN,
+    mut success: FS,
+) -> Option<(Vec<N>, usize)>
+where
+    N: Eq + Hash + Clone,
+    FN: FnMut(&N) -> IN,
+    IN: IntoIterator<Item = (N, usize)>,
+    FS: FnMut(&N) -> bool,
+{
+    let mut nodes = vec![start.clone()];
+    let mut indices = HashMap::from([(start.clone(), 0)]);
+    // (parent index, best known cost) for each node
+    let mut best = vec![(usize::MAX, 0)];
+    let ring = max_weight + 1;
+    let mut buckets = vec![Vec::new(); ring];
+    buckets[0].push(0);
+    let mut pending = 1;
+    let mut cost = 0;
+    while pending > 0 {
+        let bucket = cost % ring;
+        let Some(idx) = buckets[bucket].pop() else {
+            cost += 1;
+            continue;
+        };
+        pending -= 1;
+        if best[idx].1 != cost {
+            continue;
+        }
+        if success(&nodes[idx]) {
+            let mut path = vec![nodes[idx].clone()];
+            let mut current = idx;
+            while best[current].0 != usize::MAX {
+                current = best[current].0;
+                path.push(nodes[current].clone());
+            }
+            path.reverse();
+            return Some((path, cost));
+        }
+        for (node, weight) in successors(&nodes[idx].clone()) {
+            assert!(weight <= max_weight, "edge weight above {max_weight}");
+            let new_cost = cost + weight;
+            let next = match indices.get(&node) {
+                Some(&next) if best[next].1 <= new_cost => continue,
+                Some(&next) => next,
+                None => {
+                    nodes.push(node.clone());
+                    best.push((idx, new_cost));
+                    indices.insert(node, nodes.len() - 1);
+                    nodes.len() - 1
+                }
+            };
+            best[next] = (idx, new_cost);
+            buckets[new_cost % ring].push(next);
+            pending += 1;
+        }
+    }
+    None
+}
@@ -1,15 +1,47 @@
-use std::fs;
+use std::time::Instant;
+use std::{env, fs};
 
 use pathfinding::matrix::{directions, Matrix};
-use pathfinding::prelude::dijkstra;
+use pathfinding::prelude::{astar, dijkstra};
+
+use crate::dial::dial;
+
+mod dial;
 
 #[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 struct State {
     position: (usize, usize),
-    direction: (isize, isize),
+    /// `None` until the crucible has left the start block.
+    direction: Option<(isize, isize)>,
     distance: usize,
 }
 
+/// The movement limits and endpoints of a crucible search.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+struct CrucibleRules {
+    min_straight: usize,
+    max_straight: usize,
+    allow_reverse: bool,
+    start: (usize, usize),
+    goal: (usize, usize),
+}
+
+impl CrucibleRules {
+    /// Rules for a crucible crossing `map` from the top-left to the bottom-right block.
+    fn new(map: &Map, min_straight: usize, max_straight: usize) -> Self {
+        CrucibleRules {
+            min_straight,
+            max_straight,
+            allow_reverse: false,
+            start: (0, 0),
+            goal: (map.matrix.rows - 1, map.matrix.columns - 1),
+        }
+    }
+}
+
+/// One of the `Map::solve*` searches, for comparing them side by side.
+type Search = fn(&Map, &CrucibleRules) -> Option<(Vec<State>, usize)>;
+
 #[derive(Clone, Debug)]
 struct Map {
     matrix: Matrix<usize>,
@@ -26,84 +58,302 @@ impl From<&String> for Map {
     }
 }
 
+const DIRECTIONS: [(isize, isize); 4] =
+    [directions::N, directions::W, directions::S, directions::E];
+
 impl Map {
-    fn compute_successors<const MIN: usize, const MAX: usize>(
-        &self,
-        state: &State,
-    ) -> Vec<(State, usize)> {
+    fn compute_successors(&self, rules: &CrucibleRules, state: &State) -> Vec<(State, usize)> {
         let mut successors = Vec::new();
-        if state.direction == (0, 0) || state.distance >= MIN {
-            let possible_directions = [directions::N, directions::W, directions::S, directions::E];
-            let previous_position = self.matrix.move_in_direction(
-                state.position,
-                (-1 * state.direction.0, -1 * state.direction.1),
-            );
-            for dir in possible_directions {
-                if let Some(new_position) = self.matrix.move_in_direction(state.position, dir) {
-                    if state.direction == (0, 0) || !(new_position == previous_position.unwrap()) {
-                        let distance = match state.direction == dir {
-                            true => state.distance + 1,
-                            false => 1,
-                        };
-                        if distance <= MAX {
-                            successors.push((
-                                State {
-                                    position: new_position,
-                                    direction: dir,
-                                    distance,
-                                },
-                                *self.matrix.get(new_position).unwrap(),
-                            ))
-                        }
-                    }
-                }
+        for dir in DIRECTIONS {
+            let distance = match state.direction {
+                None => 1,
+                Some(current) if current == dir => state.distance + 1,
+                Some(_) if state.distance < rules.min_straight => continue,
+                Some(current) if current == (-dir.0, -dir.1) && !rules.allow_reverse => continue,
+                Some(_) => 1,
+            };
+            if distance > rules.max_straight {
+                continue;
             }
-        } else {
-            if let Some(position) = self
-                .matrix
-                .move_in_direction(state.position, state.direction)
-            {
-                let cost = *self.matrix.get(position).expect("valid position");
-                let new_state = State {
-                    position,
-                    direction: state.direction,
-                    distance: state.distance + 1,
-                };
-                successors.push((new_state, cost))
+            if let Some(position) = self.matrix.move_in_direction(state.position, dir) {
+                successors.push((
+                    State {
+                        position,
+                        direction: Some(dir),
+                        distance,
+                    },
+                    self.matrix[position],
+                ))
             }
         }
         successors
     }
 
-    fn solve<const MIN: usize, const MAX: usize>(&self) -> usize {
-        let start = State {
-            position: (0, 0),
-            direction: (0, 0),
+    fn start(rules: &CrucibleRules) -> State {
+        State {
+            position: rules.start,
+            direction: None,
             distance: 0,
-        };
+        }
+    }
+
+    fn is_goal(rules: &CrucibleRules, state: &State) -> bool {
+        state.position == rules.goal
+            && (state.direction.is_none() || state.distance >= rules.min_straight)
+    }
 
-        let finish = (self.matrix.rows - 1, self.matrix.columns - 1);
+    /// Dijkstra over every block, heading and run length.
+    fn solve(&self, rules: &CrucibleRules) -> Option<(Vec<State>, usize)> {
         dijkstra(
-            &start,
-            |state| self.compute_successors::<MIN, MAX>(state),
-            |state| state.position == finish && state.distance >= MIN,
+            &Map::start(rules),
+            |state| self.compute_successors(rules, state),
+            |state| Map::is_goal(rules, state),
         )
-        .expect("should be a path")
-        .1
+    }
+
+    /// The same search as `solve` using a bucket queue, as every block costs at most 9.
+    fn solve_dial(&self, rules: &CrucibleRules) -> Option<(Vec<State>, usize)> {
+        let max_weight = self.matrix.values().copied().max().unwrap_or(0);
+        dial(
+            &Map::start(rules),
+            max_weight,
+            |state| self.compute_successors(rules, state),
+            |state| Map::is_goal(rules, state),
+        )
+    }
+
+    /// A* over the same states, guided by the Manhattan distance times the cheapest block.
+    fn solve_astar(&self, rules: &CrucibleRules) -> Option<(Vec<State>, usize)> {
+        let cheapest = self.matrix.values().copied().min().unwrap_or(0);
+        astar(
+            &Map::start(rules),
+            |state| self.compute_successors(rules, state),
+            |state| {
+                (state.position.0.abs_diff(rules.goal.0) + state.position.1.abs_diff(rules.goal.1))
+                    * cheapest
+            },
+            |state| Map::is_goal(rules, state),
+        )
+    }
+
+    /// Dijkstra over `(block, axis)` nodes, where each edge is a whole straight run ending
+    /// in a turn. Falls back to `solve` when reversing is allowed, as that needs the heading.
+    fn solve_by_axis(&self, rules: &CrucibleRules) -> Option<(Vec<State>, usize)> {
+        if rules.allow_reverse {
+            return self.solve(rules);
+        }
+        // the axis is `true` after moving vertically, `false` after moving horizontally
+        let (turns, cost) = dijkstra(
+            &(rules.start, None::<bool>),
+            |&(position, axis)| {
+                let mut successors = Vec::new();
+                for dir in DIRECTIONS {
+                    let vertical = dir.1 == 0;
+                    if axis == Some(vertical) {
+                        continue;
+                    }
+                    let mut current = position;
+                    let mut cost = 0;
+                    for step in 1..=rules.max_straight {
+                        match self.matrix.move_in_direction(current, dir) {
+                            Some(next) => current = next,
+                            None => break,
+                        }
+                        cost += self.matrix[current];
+                        if step >= rules.min_straight {
+                            successors.push(((current, Some(vertical)), cost));
+                        }
+                    }
+                }
+                successors
+            },
+            |&(position, axis)| {
+                position == rules.goal && (axis.is_some() || position == rules.start)
+            },
+        )?;
+        let mut path = vec![Map::start(rules)];
+        for pair in turns.windows(2) {
+            let (from, to) = (pair[0].0, pair[1].0);
+            let dir = (
+                (to.0 as isize - from.0 as isize).signum(),
+                (to.1 as isize - from.1 as isize).signum(),
+            );
+            let mut position = from;
+            let mut distance = 0;
+            while position != to {
+                position = self.matrix.move_in_direction(position, dir).unwrap();
+                distance += 1;
+                path.push(State {
+                    position,
+                    direction: Some(dir),
+                    distance,
+                });
+            }
+        }
+        Some((path, cost))
+    }
+
+    /// The heat-loss grid with each step of `path` drawn as an arrow, like the puzzle text.
+    fn render(&self, path: &[State]) -> String {
+        let mut grid = self
+            .matrix
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|&cost| char::from_digit(cost as u32, 10).unwrap())
+                    .collect()
+            })
+            .collect::<Vec<Vec<char>>>();
+        for state in path.iter().skip(1) {
+            grid[state.position.0][state.position.1] = match state.direction {
+                Some(directions::N) => '^',
+                Some(directions::S) => 'v',
+                Some(directions::W) => '<',
+                _ => '>',
+            };
+        }
+        grid.into_iter()
+            .map(|row| row.into_iter().collect::<String>() + "\n")
+            .collect()
+    }
+
+    /// Splits `path` into its straight runs along with the heat lost on each.
+    fn segments(&self, path: &[State]) -> Vec<Segment> {
+        let mut retval: Vec<Segment> = Vec::new();
+        for pair in path.windows(2) {
+            let (from, to) = (&pair[0], &pair[1]);
+            let cost = self.matrix[to.position];
+            match retval.last_mut() {
+                Some(segment) if Some(segment.direction) == to.direction && to.distance > 1 => {
+                    segment.length += 1;
+                    segment.cost += cost;
+                }
+                _ => retval.push(Segment {
+                    start: from.position,
+                    direction: to.direction.unwrap(),
+                    length: 1,
+                    cost,
+                }),
+            }
+        }
+        retval
     }
 }
 
+/// A straight run of the crucible's path, starting from the block it turned on.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+struct Segment {
+    start: (usize, usize),
+    direction: (isize, isize),
+    length: usize,
+    cost: usize,
+}
+
+fn segments_csv(segments: &[Segment]) -> String {
+    let mut retval = String::from("row,column,direction,length,cost\n");
+    for segment in segments {
+        let direction = match segment.direction {
+            directions::N => "up",
+            directions::S => "down",
+            directions::W => "left",
+            _ => "right",
+        };
+        retval.push_str(&format!(
+            "{},{},{},{},{}\n",
+            segment.start.0, segment.start.1, direction, segment.length, segment.cost
+        ));
+    }
+    retval
+}
+
 fn part1(map: &Map) -> usize {
-    map.solve::<1, 3>()
+    map.solve_by_axis(&CrucibleRules::new(map, 1, 3))
+        .expect("should be a path")
+        .1
 }
 
 fn part2(map: &Map) -> usize {
-    map.solve::<4, 10>()
+    map.solve_by_axis(&CrucibleRules::new(map, 4, 10))
+        .expect("should be a path")
+        .1
+}
+
+/// Parses a `row,column` block that lies within `map`.
+fn parse_block(value: &str, map: &Map) -> Option<(usize, usize)> {
+    let (row, column) = value.split_once(',')?;
+    let (row, column) = (row.trim().parse().ok()?, column.trim().parse().ok()?);
+    (row < map.matrix.rows && column < map.matrix.columns).then_some((row, column))
 }
 
 fn main() {
     let input = fs::read_to_string("input.txt").expect("input.txt should exist");
     let map = Map::from(&input);
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    if args.first().map(String::as_str) == Some("path") {
+        let mut positional = Vec::new();
+        let (mut reverse, mut start, mut goal) = (false, None, None);
+        let mut iter = args.iter().skip(1);
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--reverse" => reverse = true,
+                "--start" => start = Some(iter.next()),
+                "--goal" => goal = Some(iter.next()),
+                _ => positional.push(arg),
+            }
+        }
+        let min = positional
+            .first()
+            .map_or(1, |v| v.parse().expect("min should be a number"));
+        let max = positional
+            .get(1)
+            .map_or(3, |v| v.parse().expect("max should be a number"));
+        let mut rules = CrucibleRules::new(&map, min, max);
+        rules.allow_reverse = reverse;
+        for (flag, value, block) in [
+            ("--start", start, &mut rules.start),
+            ("--goal", goal, &mut rules.goal),
+        ] {
+            let Some(value) = value else {
+                continue;
+            };
+            match value.and_then(|v| parse_block(v, &map)) {
+                Some(position) => *block = position,
+                None => {
+                    println!("{flag} should be a row,column inside the map");
+                    return;
+                }
+            }
+        }
+        let Some((path, cost)) = map.solve_astar(&rules) else {
+            println!("no path");
+            return;
+        };
+        print!("{}", map.render(&path));
+        println!("heat loss: {cost}");
+        let csv = segments_csv(&map.segments(&path));
+        match positional.get(2) {
+            Some(file) => fs::write(file, csv).expect("output should be writable"),
+            None => print!("{csv}"),
+        }
+        return;
+    }
+    if args.first().map(String::as_str) == Some("bench") {
+        for (min, max) in [(1, 3), (4, 10)] {
+            let rules = CrucibleRules::new(&map, min, max);
+            let searches: [(&str, Search); 4] = [
+                ("dijkstra", Map::solve),
+                ("dial", Map::solve_dial),
+                ("astar", Map::solve_astar),
+                ("axis", Map::solve_by_axis),
+            ];
+            for (name, search) in searches {
+                let now = Instant::now();
+                let cost = search(&map, &rules).map(|(_, cost)| cost);
+                println!("<{min}, {max}> {name}: {cost:?} in {:?}", now.elapsed());
+            }
+        }
+        return;
+    }
     println!("{}", part1(&map));
     println!("{}", part2(&map));
 }
@@ -125,4 +375,34 @@ mod tests {
         let map = Map::from(&input);
         assert_eq!(94, part2(&map));
     }
+
+    #[test]
+    fn test_segments() {
+        let input = fs::read_to_string("test.txt").expect("test.txt should exist");
+        let map = Map::from(&input);
+        let (path, cost) = map.solve(&CrucibleRules::new(&map, 1, 3)).unwrap();
+        let segments = map.segments(&path);
+        assert_eq!(cost, segments.iter().map(|s| s.cost).sum::<usize>());
+        assert!(segments.iter().all(|s| s.length <= 3));
+        assert_eq!("2>>34^>>>1323", map.render(&path).lines().next().unwrap());
+    }
+
+    #[test]
+    fn test_searches_agree() {
+        let input = fs::read_to_string("test.txt").expect("test.txt should exist");
+        let map = Map::from(&input);
+        for (min, max) in [(1, 3), (4, 10), (2, 5)] {
+            let rules = CrucibleRules::new(&map, min, max);
+            let expected = map.solve(&rules).unwrap().1;
+            assert_eq!(expected, map.solve_astar(&rules).unwrap().1);
+            assert_eq!(expected, map.solve_dial(&rules).unwrap().1);
+            let (path, cost) = map.solve_by_axis(&rules).unwrap();
+            assert_eq!(expected, cost);
+            let walked = path.iter().skip(1).map(|s| map.matrix[s.position]);
+            assert_eq!(expected, walked.sum::<usize>());
+        }
+        let mut rules = CrucibleRules::new(&map, 1, 3);
+        rules.allow_reverse = true;
+        assert!(map.solve(&rules).unwrap().1 <= 102);
+    }
 }
@@ -1,4 +1,4 @@
-use std::{fs, usize};
+use std::{env, fmt, fs};
 
 use crate::Direction::*;
 
@@ -10,14 +10,16 @@ enum Direction {
     Right,
 }
 
-impl From<&str> for Direction {
-    fn from(value: &str) -> Self {
+impl TryFrom<&str> for Direction {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
         match value {
-            "U" => Up,
-            "D" => Down,
-            "L" => Left,
-            "R" => Right,
-            _ => panic!("at the disco"),
+            "U" => Ok(Up),
+            "D" => Ok(Down),
+            "L" => Ok(Left),
+            "R" => Ok(Right),
+            _ => Err(format!("bad direction {value:?}")),
         }
     }
 }
@@ -34,6 +36,17 @@ impl From<u32> for Direction {
     }
 }
 
+impl Direction {
+    fn opposite(self) -> Direction {
+        match self {
+            Up => Down,
+            Down => Up,
+            Left => Right,
+            Right => Left,
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
 struct Point {
     x: isize,
@@ -67,131 +80,291 @@ impl Point {
 struct Trench {
     start: Point,
     end: Point,
+    direction: Direction,
     colour: usize,
+    /// The line of the dig plan this trench came from, counting from 1.
+    line: usize,
+}
+
+impl Trench {
+    fn length(&self) -> u64 {
+        self.start.x.abs_diff(self.end.x) as u64 + self.start.y.abs_diff(self.end.y) as u64
+    }
+
+    fn intersects(&self, other: &Trench) -> bool {
+        let overlaps = |a: (isize, isize), b: (isize, isize)| {
+            a.0.min(a.1).max(b.0.min(b.1)) <= a.0.max(a.1).min(b.0.max(b.1))
+        };
+        overlaps((self.start.x, self.end.x), (other.start.x, other.end.x))
+            && overlaps((self.start.y, self.end.y), (other.start.y, other.end.y))
+    }
+}
+
+/// How each line of the dig plan encodes its direction and distance.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+enum Encoding {
+    /// The `R 6` columns.
+    Direct,
+    /// The `(#70c710)` column: five hex digits of distance, then one digit of direction.
+    Hex,
+}
+
+/// Something that makes a dig plan unsuitable for measuring its lagoon.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+enum PlanError {
+    Unclosed { end: Point },
+    Backtrack { line: usize },
+    SelfIntersection { line: usize, other: usize },
+}
+
+impl fmt::Display for PlanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlanError::Unclosed { end } => {
+                write!(
+                    f,
+                    "plan ends at ({}, {}) instead of the start",
+                    end.x, end.y
+                )
+            }
+            PlanError::Backtrack { line } => {
+                write!(f, "line {line} doubles back over the previous trench")
+            }
+            PlanError::SelfIntersection { line, other } => {
+                write!(f, "line {line} crosses line {other}")
+            }
+        }
+    }
 }
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
-struct Grid {
+struct DigPlan {
     trenches: Vec<Trench>,
 }
 
-impl Grid {
-    fn new() -> Grid {
-        Grid {
-            trenches: Vec::new(),
+fn parse_step(line: &str, encoding: Encoding) -> Result<(Direction, isize, usize), String> {
+    let mut splits = line.split(' ');
+    let direction = splits.next().ok_or("no direction")?;
+    let distance = splits.next().ok_or("no distance")?;
+    let colour = splits
+        .next()
+        .ok_or("no colour")?
+        .strip_prefix("(#")
+        .and_then(|c| c.strip_suffix(')'))
+        .filter(|c| c.len() == 6)
+        .ok_or("colour should look like (#rrggbb)")?;
+    let colour = usize::from_str_radix(colour, 16).map_err(|e| format!("bad colour: {e}"))?;
+    let (direction, distance) = match encoding {
+        Encoding::Direct => {
+            let direction = Direction::try_from(direction)?;
+            let distance = distance
+                .parse::<isize>()
+                .map_err(|e| format!("bad distance: {e}"))?;
+            (direction, distance)
+        }
+        Encoding::Hex => {
+            let direction = match colour & 0xf {
+                0..=3 => Direction::from((colour & 0xf) as u32),
+                d => return Err(format!("bad direction digit {d}")),
+            };
+            (direction, (colour >> 4) as isize)
         }
+    };
+    if distance <= 0 {
+        return Err(format!("distance should be positive, got {distance}"));
     }
+    Ok((direction, distance, colour))
+}
 
-    fn swap(&mut self) {
-        let mut new_trenches = Vec::new();
+impl DigPlan {
+    fn parse(input: &str, encoding: Encoding) -> Result<DigPlan, String> {
         let mut current = Point { x: 0, y: 0 };
-        for trench in &self.trenches {
-            let inst = format!("{:06x}", trench.colour);
-            let distance = isize::from_str_radix(&inst[..5], 16).expect("should be a number");
-            let direction = Direction::from(
-                inst.chars()
-                    .last()
-                    .unwrap()
-                    .to_digit(10)
-                    .expect("should be a number"),
-            );
+        let mut trenches = Vec::new();
+        for (idx, line) in input.lines().enumerate() {
+            let (direction, distance, colour) =
+                parse_step(line, encoding).map_err(|e| format!("line {}: {e}", idx + 1))?;
             let next = current.travel(direction, distance);
-            new_trenches.push(Trench {
+            trenches.push(Trench {
                 start: current,
                 end: next,
-                colour: 0,
+                direction,
+                colour,
+                line: idx + 1,
             });
-            current = next
+            current = next;
         }
-        self.trenches = new_trenches;
+        if trenches.is_empty() {
+            return Err("empty dig plan".to_string());
+        }
+        Ok(DigPlan { trenches })
     }
 
-    fn calculate_perimeter(&self) -> usize {
-        let mut vertices = Vec::new();
-        vertices.push(self.trenches[0].start);
-        for trench in &self.trenches {
-            vertices.push(trench.end)
+    /// Everything wrong with the plan as a closed, simple polygon, in line order.
+    fn validate(&self) -> Vec<PlanError> {
+        let mut errors = Vec::new();
+        let count = self.trenches.len();
+        let end = self.trenches[count - 1].end;
+        let closed = end == self.trenches[0].start;
+        if !closed {
+            errors.push(PlanError::Unclosed { end });
         }
-        let mut perimeter = 0;
-        for i in 0..vertices.len() - 1 {
-            perimeter += (vertices[i + 1].x - vertices[i].x).abs()
-                + (vertices[i + 1].y - vertices[i].y).abs();
+        for (i, trench) in self.trenches.iter().enumerate() {
+            let previous = match i {
+                0 if closed => Some(&self.trenches[count - 1]),
+                0 => None,
+                _ => Some(&self.trenches[i - 1]),
+            };
+            if previous.is_some_and(|p| p.direction == trench.direction.opposite()) {
+                errors.push(PlanError::Backtrack { line: trench.line });
+            }
+            for (j, other) in self.trenches.iter().enumerate().skip(i + 2) {
+                if closed && i == 0 && j == count - 1 {
+                    continue;
+                }
+                if trench.intersects(other) {
+                    errors.push(PlanError::SelfIntersection {
+                        line: other.line,
+                        other: trench.line,
+                    });
+                }
+            }
         }
-        perimeter += (vertices.last().unwrap().x - vertices[0].x).abs()
-            + (vertices.last().unwrap().y - vertices[0].y).abs();
-        perimeter as usize
+        errors.sort_by_key(|e| match e {
+            PlanError::Unclosed { .. } => usize::MAX,
+            PlanError::Backtrack { line } | PlanError::SelfIntersection { line, .. } => *line,
+        });
+        errors
     }
 
-    fn calculate_area(&self) -> usize {
-        let mut vertices = Vec::new();
-        vertices.push(self.trenches[0].start);
-        for trench in &self.trenches {
-            vertices.push(trench.end)
-        }
-        let mut area = 0;
-        for i in 0..vertices.len() - 1 {
-            area += vertices[i].x * vertices[i + 1].y - vertices[i].y * vertices[i + 1].x;
-            if i % 2 == 0 {
-                //area += 1;
-            }
+    fn calculate_perimeter(&self) -> u64 {
+        self.trenches.iter().map(Trench::length).sum()
+    }
+
+    /// The area enclosed by the trench centre lines, by the shoelace formula.
+    fn calculate_area(&self) -> u64 {
+        let twice_area = self
+            .trenches
+            .iter()
+            .map(|t| t.start.x as i128 * t.end.y as i128 - t.start.y as i128 * t.end.x as i128)
+            .sum::<i128>();
+        (twice_area.unsigned_abs() / 2) as u64
+    }
+
+    /// Cubic metres of lava the dug-out lagoon holds, trench included.
+    fn lagoon_size(&self) -> Result<u64, Vec<PlanError>> {
+        let errors = self.validate();
+        if !errors.is_empty() {
+            return Err(errors);
         }
-        area +=
-            vertices.last().unwrap().x * vertices[0].y - vertices[0].x * vertices.last().unwrap().y;
-        area as usize / 2
+        Ok(self.calculate_area() + self.calculate_perimeter() / 2 + 1)
     }
-}
 
-impl From<String> for Grid {
-    fn from(value: String) -> Self {
-        let mut current = Point { x: 0, y: 0 };
-        let mut grid = Grid::new();
-        for line in value.lines() {
-            let mut splits = line.split(" ");
-            let direction = Direction::from(splits.next().expect("no direction"));
-            let distance = splits
-                .next()
-                .expect("no distance")
-                .parse::<isize>()
-                .expect("should be a number");
-            let colour = usize::from_str_radix(
-                splits
-                    .next()
-                    .expect("no colour")
-                    .trim_start_matches("(#")
-                    .trim_end_matches(")"),
-                16,
+    /// Draws the dig plan as an SVG `size` pixels across, filling the lagoon and stroking each
+    /// trench in its own colour. Coordinates are scaled down so huge plans still fit.
+    fn to_svg(&self, size: f64) -> String {
+        let points = self.trenches.iter().map(|t| t.start);
+        let min_x = points.clone().map(|p| p.x).min().unwrap_or(0);
+        let max_x = points.clone().map(|p| p.x).max().unwrap_or(0);
+        let min_y = points.clone().map(|p| p.y).min().unwrap_or(0);
+        let max_y = points.map(|p| p.y).max().unwrap_or(0);
+        let extent = (max_x - min_x).max(max_y - min_y).max(1) as f64;
+        let margin = size / 50.0;
+        let scale = (size - 2.0 * margin) / extent;
+        let project = |p: Point| {
+            (
+                margin + (p.x - min_x) as f64 * scale,
+                margin + (p.y - min_y) as f64 * scale,
             )
-            .expect("should be a hex string");
-            let next = current.travel(direction, distance);
-            grid.trenches.push(Trench {
-                start: current,
-                end: next,
-                colour,
-            });
-            current = next;
+        };
+        let width = 2.0 * margin + (max_x - min_x) as f64 * scale;
+        let height = 2.0 * margin + (max_y - min_y) as f64 * scale;
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width:.0}\" height=\"{height:.0}\" viewBox=\"0 0 {width:.2} {height:.2}\">\n"
+        );
+        let polygon = self
+            .trenches
+            .iter()
+            .map(|t| {
+                let (x, y) = project(t.start);
+                format!("{x:.2},{y:.2}")
+            })
+            .collect::<Vec<String>>()
+            .join(" ");
+        svg.push_str(&format!(
+            "  <polygon points=\"{polygon}\" fill=\"#d9d9d9\" stroke=\"none\"/>\n"
+        ));
+        let stroke = (size / 250.0).max(1.0);
+        for trench in &self.trenches {
+            let (x1, y1) = project(trench.start);
+            let (x2, y2) = project(trench.end);
+            svg.push_str(&format!(
+                "  <line x1=\"{x1:.2}\" y1=\"{y1:.2}\" x2=\"{x2:.2}\" y2=\"{y2:.2}\" stroke=\"#{:06x}\" stroke-width=\"{stroke:.2}\" stroke-linecap=\"square\"/>\n",
+                trench.colour
+            ));
         }
-        grid
+        svg.push_str("</svg>\n");
+        svg
     }
 }
 
-fn part1(grid: Grid) -> usize {
-    let area = grid.calculate_area();
-    let perimeter = grid.calculate_perimeter();
-    area + (perimeter / 2) + 1
+fn part1(input: &str) -> u64 {
+    DigPlan::parse(input, Encoding::Direct)
+        .expect("should be a dig plan")
+        .lagoon_size()
+        .expect("should be a closed loop")
 }
 
-fn part2(mut grid: Grid) -> usize {
-    grid.swap();
-    let area = grid.calculate_area();
-    let perimeter = grid.calculate_perimeter();
-    area + (perimeter / 2) + 1
+fn part2(input: &str) -> u64 {
+    DigPlan::parse(input, Encoding::Hex)
+        .expect("should be a dig plan")
+        .lagoon_size()
+        .expect("should be a closed loop")
 }
 
 fn main() {
     let input = fs::read_to_string("input.txt").expect("input.txt should exist");
-    let grid = Grid::from(input);
-    println!("{}", part1(grid.clone()));
-    println!("{}", part2(grid.clone()));
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    let encoding = match args.get(1).map(String::as_str) {
+        None | Some("1") | Some("direct") => Some(Encoding::Direct),
+        Some("2") | Some("hex") => Some(Encoding::Hex),
+        _ => None,
+    };
+    match args.first().map(String::as_str) {
+        Some("svg") => {
+            let Some(encoding) = encoding else {
+                println!("usage: svg <direct|hex> [file]");
+                return;
+            };
+            let plan = DigPlan::parse(&input, encoding).expect("should be a dig plan");
+            let svg = plan.to_svg(1000.0);
+            match args.get(2) {
+                Some(file) => fs::write(file, svg).expect("output should be writable"),
+                None => print!("{svg}"),
+            }
+        }
+        Some("validate") => {
+            let Some(encoding) = encoding else {
+                println!("usage: validate <direct|hex>");
+                return;
+            };
+            match DigPlan::parse(&input, encoding) {
+                Ok(plan) => {
+                    let errors = plan.validate();
+                    if errors.is_empty() {
+                        println!("ok");
+                    }
+                    for error in errors {
+                        println!("{error}");
+                    }
+                }
+                Err(e) => println!("{e}"),
+            }
+        }
+        _ => {
+            println!("{}", part1(&input));
+            println!("{}", part2(&input));
+        }
+    }
 }
 
 #[cfg(test)]
@@ -201,14 +374,40 @@ mod tests {
     #[test]
     fn test_p1() {
         let input = fs::read_to_string("test.txt").expect("test.txt should exist");
-        let grid = Grid::from(input);
-        assert_eq!(62, part1(grid));
+        assert_eq!(62, part1(&input));
     }
 
     #[test]
     fn test_p2() {
         let input = fs::read_to_string("test.txt").expect("test.txt should exist");
-        let grid = Grid::from(input);
-        assert_eq!(952408144115, part2(grid));
+        assert_eq!(952408144115, part2(&input));
+    }
+
+    #[test]
+    fn test_validate() {
+        let input = fs::read_to_string("test.txt").expect("test.txt should exist");
+        assert!(DigPlan::parse(&input, Encoding::Direct)
+            .unwrap()
+            .validate()
+            .is_empty());
+
+        assert_eq!(
+            Some("line 2: distance should be positive, got -3".to_string()),
+            DigPlan::parse("R 4 (#000000)\nR -3 (#000000)", Encoding::Direct).err()
+        );
+        assert!(DigPlan::parse("R 0 (#000000)", Encoding::Direct).is_err());
+
+        let broken = "R 4 (#000000)\nD 2 (#000000)\nU 1 (#000000)\nL 4 (#000000)\nD 3 (#000000)";
+        let errors = DigPlan::parse(broken, Encoding::Direct).unwrap().validate();
+        assert!(errors.contains(&PlanError::Backtrack { line: 3 }));
+        assert!(errors.contains(&PlanError::Unclosed {
+            end: Point { x: 0, y: 4 }
+        }));
+
+        let crossing = "R 2 (#000000)\nD 2 (#000000)\nL 1 (#000000)\nU 3 (#000000)\nL 1 (#000000)\nD 1 (#000000)";
+        let errors = DigPlan::parse(crossing, Encoding::Direct)
+            .unwrap()
+            .validate();
+        assert!(errors.contains(&PlanError::SelfIntersection { line: 4, other: 1 }));
     }
 }
@@ -1,7 +1,5 @@
-use std::cmp::max;
-use std::fs;
-
-use petgraph::{algo, prelude::*};
+use std::collections::HashSet;
+use std::{env, fs};
 
 use crate::Tile::*;
 
@@ -15,6 +13,19 @@ enum Tile {
     WSlope,
 }
 
+impl From<Tile> for char {
+    fn from(value: Tile) -> Self {
+        match value {
+            Path => '.',
+            Forest => '#',
+            NSlope => '^',
+            ESlope => '>',
+            SSlope => 'v',
+            WSlope => '<',
+        }
+    }
+}
+
 impl From<char> for Tile {
     fn from(value: char) -> Self {
         match value {
@@ -29,24 +40,56 @@ impl From<char> for Tile {
     }
 }
 
-#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
-struct State {
-    position: (usize, usize),
-}
-
 #[derive(Clone, Debug)]
 struct Map {
     start: (usize, usize),
     finish: (usize, usize),
-    digraph: DiGraphMap<(usize, usize), usize>,
-    ungraph: UnGraphMap<(usize, usize), usize>,
+    tiles: Vec<Vec<Tile>>,
+    no_slopes: Vec<Vec<Tile>>,
+}
+
+/// The maze contracted to its junctions, joined by the corridors between them.
+#[derive(Clone, Debug)]
+struct JunctionGraph {
+    /// Position of each junction; the start is always 0 and the finish 1.
+    junctions: Vec<(usize, usize)>,
+    /// The corridors leaving each junction.
+    edges: Vec<Vec<Corridor>>,
+}
+
+#[derive(Clone, Debug)]
+struct Corridor {
+    to: usize,
+    /// Every tile walked after leaving the junction, ending on the junction reached.
+    tiles: Vec<(usize, usize)>,
+}
+
+impl Corridor {
+    fn length(&self) -> usize {
+        self.tiles.len()
+    }
+}
+
+/// A route from the start to the finish.
+#[derive(Clone, Debug)]
+struct Hike {
+    /// Each junction passed, starting at the start, with the steps taken to reach it.
+    junctions: Vec<((usize, usize), usize)>,
+    /// Every tile stepped on after the start.
+    tiles: Vec<(usize, usize)>,
+}
+
+impl Hike {
+    fn length(&self) -> usize {
+        self.tiles.len()
+    }
 }
 
 impl From<&String> for Map {
     fn from(value: &String) -> Self {
         let map = value
             .lines()
-            .map(|line| line.chars().map(|c| Tile::from(c)).collect())
+            .map(|line| line.chars().map(Tile::from).collect())
             .collect::<Vec<Vec<Tile>>>();
         let no_slopes = map
             .iter()
@@ -76,84 +119,52 @@ impl From<&String> for Map {
             }
         }
 
-        let mut digraph = DiGraphMap::<(usize, usize), usize>::new();
-        let mut ungraph = UnGraphMap::<(usize, usize), usize>::new();
-
-        for y in 0..map.len() {
-            for x in 0..map[y].len() {
-                if map[y][x] != Forest {
-                    for neighbour in calculate_neighbours(&map, x, y) {
-                        digraph.add_edge((x, y), neighbour, 1);
-                    }
-                    for neighbour in calculate_neighbours(&no_slopes, x, y) {
-                        ungraph.add_edge((x, y), neighbour, 1);
-                    }
-                }
-            }
-        }
-
         Map {
             start,
             finish,
-            digraph,
-            ungraph,
+            tiles: map,
+            no_slopes,
         }
     }
 }
 
-fn calculate_neighbours(map: &Vec<Vec<Tile>>, x: usize, y: usize) -> Vec<(usize, usize)> {
+fn calculate_neighbours(map: &[Vec<Tile>], x: usize, y: usize) -> Vec<(usize, usize)> {
     let mut retval = Vec::new();
     let tile = map[y][x];
     match tile {
         Path => {
-            if x != 0 {
-                if ![Forest, ESlope].contains(&map[y][x - 1]) {
-                    retval.push((x - 1, y));
-                }
+            if x != 0 && ![Forest, ESlope].contains(&map[y][x - 1]) {
+                retval.push((x - 1, y));
             }
-            if y != 0 {
-                if ![Forest, SSlope].contains(&map[y - 1][x]) {
-                    retval.push((x, y - 1));
-                }
+            if y != 0 && ![Forest, SSlope].contains(&map[y - 1][x]) {
+                retval.push((x, y - 1));
             }
-            if x != map[0].len() - 1 {
-                if ![Forest, WSlope].contains(&map[y][x + 1]) {
-                    retval.push((x + 1, y));
-                }
+            if x != map[0].len() - 1 && ![Forest, WSlope].contains(&map[y][x + 1]) {
+                retval.push((x + 1, y));
             }
-            if y != map.len() - 1 {
-                if ![Forest, NSlope].contains(&map[y + 1][x]) {
-                    retval.push((x, y + 1));
-                }
+            if y != map.len() - 1 && ![Forest, NSlope].contains(&map[y + 1][x]) {
+                retval.push((x, y + 1));
             }
         }
         Forest => (),
         NSlope => {
-            if y != 0 {
-                if ![Forest, SSlope].contains(&map[y - 1][x]) {
-                    retval.push((x, y - 1));
-                }
+            if y != 0 && ![Forest, SSlope].contains(&map[y - 1][x]) {
+                retval.push((x, y - 1));
             }
         }
         ESlope => {
-            if x != map[0].len() - 1 {
-                if ![Forest, WSlope].contains(&map[y][x + 1]) {
-                    retval.push((x + 1, y));
-                }
+            if x != map[0].len() - 1 && ![Forest, WSlope].contains(&map[y][x + 1]) {
+                retval.push((x + 1, y));
             }
         }
         SSlope => {
-            if y != map.len() - 1 {
-                if ![Forest, NSlope].contains(&map[y + 1][x]) {
-                    retval.push((x, y + 1));
-                }
+            if y != map.len() - 1 && ![Forest, NSlope].contains(&map[y + 1][x]) {
+                retval.push((x, y + 1));
             }
         }
         WSlope => {
-            if x != 0 {
-                if ![Forest, ESlope].contains(&map[y][x - 1]) {
-                    retval.push((x - 1, y));
-                }
+            if x != 0 && ![Forest, ESlope].contains(&map[y][x - 1]) {
+                retval.push((x - 1, y));
             }
         }
     }
@@ -161,22 +172,158 @@ fn calculate_neighbours(map: &Vec<Vec<Tile>>, x: usize, y: usize) -> Vec<(usize,
 }
 
 impl Map {
+    /// Contracts the maze, following slopes only when `directed`.
+    fn junction_graph(&self, directed: bool) -> JunctionGraph {
+        let map = if directed {
+            &self.tiles
+        } else {
+            &self.no_slopes
+        };
+        let mut junctions = vec![self.start, self.finish];
+        for (y, row) in self.no_slopes.iter().enumerate() {
+            for (x, &tile) in row.iter().enumerate() {
+                if tile != Forest && calculate_neighbours(&self.no_slopes, x, y).len() >= 3 {
+                    junctions.push((x, y));
+                }
+            }
+        }
+        let mut edges = vec![Vec::new(); junctions.len()];
+        for (from, &(x, y)) in junctions.iter().enumerate() {
+            for first in calculate_neighbours(map, x, y) {
+                let mut previous = (x, y);
+                let mut current = first;
+                let mut tiles = vec![first];
+                // walk the corridor until it reaches another junction or dead-ends
+                loop {
+                    if let Some(to) = junctions.iter().position(|&j| j == current) {
+                        edges[from].push(Corridor { to, tiles });
+                        break;
+                    }
+                    let next = calculate_neighbours(map, current.0, current.1)
+                        .into_iter()
+                        .find(|&n| n != previous);
+                    match next {
+                        Some(next) => {
+                            previous = current;
+                            current = next;
+                            tiles.push(next);
+                        }
+                        None => break,
+                    }
+                }
+            }
+        }
+        JunctionGraph { junctions, edges }
+    }
+
     fn solve(&self, directed: bool) -> usize {
-        let mut distance = 0;
-        if directed {
-            for path in
-                algo::all_simple_paths::<Vec<_>, _>(&self.digraph, self.start, self.finish, 0, None)
-            {
-                distance = max(distance, path.len());
+        self.longest_hike(directed)
+            .expect("should be a path to the finish")
+            .length()
+    }
+
+    fn longest_hike(&self, directed: bool) -> Option<Hike> {
+        let graph = self.junction_graph(directed);
+        let route = graph.longest_route()?;
+        let mut hike = Hike {
+            junctions: vec![(self.start, 0)],
+            tiles: Vec::new(),
+        };
+        for (from, edge) in route {
+            let corridor = &graph.edges[from][edge];
+            hike.junctions
+                .push((graph.junctions[corridor.to], corridor.length()));
+            hike.tiles.extend_from_slice(&corridor.tiles);
+        }
+        Some(hike)
+    }
+
+    /// The map with the hike drawn over it as `O`, like the puzzle, optionally in colour.
+    fn render(&self, hike: &Hike, colour: bool) -> String {
+        let mut retval = String::new();
+        let walked = hike
+            .tiles
+            .iter()
+            .copied()
+            .collect::<HashSet<(usize, usize)>>();
+        for (y, row) in self.tiles.iter().enumerate() {
+            for (x, &tile) in row.iter().enumerate() {
+                if (x, y) == self.start {
+                    retval.push('S');
+                } else if walked.contains(&(x, y)) {
+                    retval.push_str(if colour { "\x1b[1;33mO\x1b[0m" } else { "O" });
+                } else {
+                    retval.push(char::from(tile));
+                }
             }
-        } else {
-            for path in
-                algo::all_simple_paths::<Vec<_>, _>(&self.ungraph, self.start, self.finish, 0, None)
-            {
-                distance = max(distance, path.len());
+            retval.push('\n');
+        }
+        retval
+    }
+}
+
+impl JunctionGraph {
+    /// The longest simple route from the start to the finish, as `(junction, corridor index)`
+    /// for each corridor taken.
+    fn longest_route(&self) -> Option<Vec<(usize, usize)>> {
+        assert!(
+            self.junctions.len() <= 64,
+            "too many junctions for a u64 mask"
+        );
+        // the finish is a dead end, so reaching the junction before it means taking that edge
+        let last = (0..self.edges.len())
+            .filter_map(|from| {
+                self.edges[from]
+                    .iter()
+                    .position(|corridor| corridor.to == 1)
+                    .map(|edge| (from, edge))
+            })
+            .collect::<Vec<(usize, usize)>>();
+        let mut best = None;
+        self.search(0, 1, 0, &last, &mut Vec::new(), &mut best);
+        best.map(|(_, route)| route)
+    }
+
+    fn search(
+        &self,
+        node: usize,
+        visited: u64,
+        distance: usize,
+        last: &[(usize, usize)],
+        route: &mut Vec<(usize, usize)>,
+        best: &mut Option<(usize, Vec<(usize, usize)>)>,
+    ) {
+        let mut finish = |distance: usize, route: &Vec<(usize, usize)>| {
+            if best.as_ref().is_none_or(|(longest, _)| distance > *longest) {
+                *best = Some((distance, route.clone()));
+            }
+        };
+        if node == 1 {
+            finish(distance, route);
+            return;
+        }
+        if let [(penultimate, edge)] = last {
+            if node == *penultimate {
+                route.push((node, *edge));
+                finish(distance + self.edges[node][*edge].length(), route);
+                route.pop();
+                return;
+            }
+        }
+        for (edge, corridor) in self.edges[node].iter().enumerate() {
+            if visited & (1 << corridor.to) == 0 {
+                route.push((node, edge));
+                self.search(
+                    corridor.to,
+                    visited | (1 << corridor.to),
+                    distance + corridor.length(),
+                    last,
+                    route,
+                    best,
+                );
+                route.pop();
             }
         }
-        distance - 1
     }
 }
 
@@ -191,6 +338,21 @@ fn part2(map: &Map) -> usize {
 fn main() {
     let input = fs::read_to_string("input.txt").expect("input.txt should exist");
     let map = Map::from(&input);
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    if args.first().map(String::as_str) == Some("hike") {
+        let directed = args.get(1).map(String::as_str) != Some("2");
+        let Some(hike) = map.longest_hike(directed) else {
+            println!("no path to the finish");
+            return;
+        };
+        let colour = args.iter().any(|arg| arg == "--colour");
+        print!("{}", map.render(&hike, colour));
+        println!("length: {}", hike.length());
+        for ((x, y), steps) in &hike.junctions {
+            println!("{x},{y},{steps}");
+        }
+        return;
+    }
     println!("{}", part1(&map));
     println!("{}", part2(&map));
 }
@@ -212,4 +374,19 @@ mod tests {
         let map = Map::from(&input);
         assert_eq!(154, part2(&map));
     }
+
+    #[test]
+    fn test_hike() {
+        let input = fs::read_to_string("test.txt").expect("test.txt should exist");
+        let map = Map::from(&input);
+        let hike = map.longest_hike(true).unwrap();
+        assert_eq!(map.finish, *hike.tiles.last().unwrap());
+        assert_eq!(
+            hike.length(),
+            hike.junctions.iter().map(|(_, steps)| steps).sum::<usize>()
+        );
+        let rendered = map.render(&hike, false);
+        assert_eq!(hike.length(), rendered.matches('O').count());
+        assert!(rendered.starts_with("#S#####"));
+    }
 }
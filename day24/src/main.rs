@@ -1,17 +1,22 @@
-use std::fs;
+use std::{env, fmt, fs};
 
-use rgeometry::data::{Line, Point};
-use z3::ast::{Ast, Int};
-use z3::{Config, Context, SatResult::*, Solver};
+#[cfg(feature = "z3")]
+use z3::ast::Int;
+#[cfg(feature = "z3")]
+use z3::{SatResult::*, Solver};
 
-#[derive(Copy, Clone, Debug, Default, PartialEq, PartialOrd)]
+use crate::ratio::Ratio;
+
+mod ratio;
+
+#[derive(Copy, Clone, Debug, Default, Eq, Hash, PartialEq)]
 struct Snowball {
-    px: f64,
-    py: f64,
-    pz: f64,
-    vx: f64,
-    vy: f64,
-    vz: f64,
+    px: i64,
+    py: i64,
+    pz: i64,
+    vx: i64,
+    vy: i64,
+    vz: i64,
 }
 
 impl From<&str> for Snowball {
@@ -22,13 +27,13 @@ impl From<&str> for Snowball {
             .unwrap()
             .split(", ")
             .map(|item| item.trim().parse().unwrap())
-            .collect::<Vec<f64>>();
+            .collect::<Vec<i64>>();
         let velocities = splits
             .next()
             .unwrap()
             .split(", ")
             .map(|item| item.trim().parse().unwrap())
-            .collect::<Vec<f64>>();
+            .collect::<Vec<i64>>();
         let (px, py, pz) = (positions[0], positions[1], positions[2]);
         let (vx, vy, vz) = (velocities[0], velocities[1], velocities[2]);
         Snowball {
@@ -42,11 +47,11 @@ impl From<&str> for Snowball {
     }
 }
 
-#[derive(Clone, Debug, Default, PartialEq, PartialOrd)]
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
 struct Snowballs {
     snowballs: Vec<Snowball>,
-    min: f64,
-    max: f64,
+    min: i64,
+    max: i64,
 }
 
 impl From<&String> for Snowballs {
@@ -57,65 +62,99 @@ impl From<&String> for Snowballs {
         }
         Snowballs {
             snowballs,
-            min: 0f64,
-            max: 0f64,
+            min: 0,
+            max: 0,
         }
     }
 }
 
-fn has_same_sign(a: f64, b: f64) -> bool {
-    (a < 0f64 && b < 0f64) || (a > 0f64 && b > 0f64)
+/// What happens between the paths of two hailstones, ignoring the Z axis.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+enum Crossing {
+    Inside {
+        x: Ratio,
+        y: Ratio,
+    },
+    Outside {
+        x: Ratio,
+        y: Ratio,
+    },
+    /// The paths crossed before `a`, `b` or both were at their starting positions.
+    Past {
+        a: bool,
+        b: bool,
+    },
+    Parallel,
+    /// Both hailstones travel along the same line.
+    Coincident,
 }
 
-fn in_past(intersection: &Point<f64>, a: &Snowball, b: &Snowball) -> bool {
-    let mut retval = false;
-    let &x = intersection.x_coord();
-    let &y = intersection.y_coord();
-    let tests = [
-        ((x - a.px, y - a.py), (a.vx, a.vy)),
-        ((x - b.px, y - b.py), (b.vz, b.vy)),
-    ];
-    for test in tests {
-        if !has_same_sign(test.0 .0, test.1 .0) || !has_same_sign(test.0 .1, test.1 .1) {
-            retval = true;
-            break;
+impl Snowballs {
+    fn crossing(&self, a: &Snowball, b: &Snowball) -> Crossing {
+        let det = a.vx as i128 * b.vy as i128 - a.vy as i128 * b.vx as i128;
+        let (dx, dy) = ((b.px - a.px) as i128, (b.py - a.py) as i128);
+        if det == 0 {
+            return if dx * a.vy as i128 == dy * a.vx as i128 {
+                Crossing::Coincident
+            } else {
+                Crossing::Parallel
+            };
+        }
+        // solve a.p + a.v * t = b.p + b.v * u by Cramer's rule
+        let t = Ratio::new(dx * b.vy as i128 - dy * b.vx as i128, det);
+        let u = Ratio::new(dx * a.vy as i128 - dy * a.vx as i128, det);
+        if t < Ratio::zero() || u < Ratio::zero() {
+            return Crossing::Past {
+                a: t < Ratio::zero(),
+                b: u < Ratio::zero(),
+            };
+        }
+        let x = Ratio::from(a.px) + Ratio::from(a.vx) * t.clone();
+        let y = Ratio::from(a.py) + Ratio::from(a.vy) * t;
+        let (min, max) = (Ratio::from(self.min), Ratio::from(self.max));
+        if min <= x && x <= max && min <= y && y <= max {
+            Crossing::Inside { x, y }
+        } else {
+            Crossing::Outside { x, y }
         }
     }
-    retval
-}
 
-impl Snowballs {
-    fn intersections(&self) -> usize {
-        let mut intersections = 0;
-        for i in 0..self.snowballs.len() {
-            let p1 = Point::<f64, 2>::new([self.snowballs[i].px, self.snowballs[i].py]);
-            let p2 = Point::<f64, 2>::new([
-                self.snowballs[i].px + self.snowballs[i].vx,
-                self.snowballs[i].py + self.snowballs[i].vy,
-            ]);
-            let test = Line::new_through(&p1, &p2);
-            for j in i + 1..self.snowballs.len() {
-                let p1 = Point::<f64, 2>::new([self.snowballs[j].px, self.snowballs[j].py]);
-                let p2 = Point::<f64, 2>::new([
-                    self.snowballs[j].px + self.snowballs[j].vx,
-                    self.snowballs[j].py + self.snowballs[j].vy,
-                ]);
-                let check = Line::new_through(&p1, &p2);
-                if let Some(point) = test.intersection_point(&check) {
-                    let &x = point.x_coord();
-                    let &y = point.y_coord();
-                    if self.min <= x
-                        && x <= self.max
-                        && self.min <= y
-                        && y <= self.max
-                        && !in_past(&point, &self.snowballs[i], &self.snowballs[j])
-                    {
-                        intersections += 1;
-                    }
-                }
+    /// The crossing for every pair of hailstones as `(a, b, crossing)`.
+    fn crossings(&self) -> Vec<(usize, usize, Crossing)> {
+        let mut retval = Vec::new();
+        for (i, a) in self.snowballs.iter().enumerate() {
+            for (j, b) in self.snowballs.iter().enumerate().skip(i + 1) {
+                retval.push((i, j, self.crossing(a, b)));
             }
         }
-        intersections
+        retval
+    }
+
+    fn intersections(&self) -> usize {
+        self.crossings()
+            .into_iter()
+            .filter(|(_, _, crossing)| matches!(crossing, Crossing::Inside { .. }))
+            .count()
+    }
+}
+
+fn describe(crossing: &Crossing) -> String {
+    match crossing {
+        Crossing::Inside { x, y } => format!(
+            "cross inside the test area (at x={:.3}, y={:.3})",
+            x.to_f64(),
+            y.to_f64()
+        ),
+        Crossing::Outside { x, y } => format!(
+            "cross outside the test area (at x={:.3}, y={:.3})",
+            x.to_f64(),
+            y.to_f64()
+        ),
+        Crossing::Past { a: true, b: true } => "crossed in the past for both hailstones".into(),
+        Crossing::Past { a: true, .. } => "crossed in the past for hailstone A".into(),
+        Crossing::Past { .. } => "crossed in the past for hailstone B".into(),
+        Crossing::Parallel => "are parallel; they never intersect".into(),
+        Crossing::Coincident => "travel along the same path".into(),
     }
 }
 
@@ -123,70 +162,278 @@ fn part1(snowballs: &Snowballs) -> usize {
     snowballs.intersections()
 }
 
+impl Snowball {
+    /// When `other` is at the same place as this one, if ever.
+    fn collision_time(&self, other: &Snowball) -> Option<Ratio> {
+        let axes = [
+            (other.px - self.px, self.vx - other.vx),
+            (other.py - self.py, self.vy - other.vy),
+            (other.pz - self.pz, self.vz - other.vz),
+        ];
+        let mut time = None;
+        for (dp, dv) in axes {
+            if dv == 0 {
+                if dp != 0 {
+                    return None;
+                }
+                continue;
+            }
+            let t = Ratio::new(dp as i128, dv as i128);
+            match &time {
+                Some(existing) if *existing != t => return None,
+                _ => time = Some(t),
+            }
+        }
+        // identical trajectories meet at every moment, including the start
+        Some(time.unwrap_or_else(Ratio::zero))
+    }
+}
+
+/// The three equations `P×(va - vb) + (pa - pb)×V = pa×va - pb×vb` that a rock at `P` with
+/// velocity `V` satisfies when it hits both `a` and `b`, as rows over `[P, V | rhs]`.
+fn rock_equations(a: &Snowball, b: &Snowball) -> [[i128; 7]; 3] {
+    let (pa, va) = (
+        [a.px as i128, a.py as i128, a.pz as i128],
+        [a.vx as i128, a.vy as i128, a.vz as i128],
+    );
+    let (pb, vb) = (
+        [b.px as i128, b.py as i128, b.pz as i128],
+        [b.vx as i128, b.vy as i128, b.vz as i128],
+    );
+    let cross = |u: [i128; 3], v: [i128; 3]| {
+        [
+            u[1] * v[2] - u[2] * v[1],
+            u[2] * v[0] - u[0] * v[2],
+            u[0] * v[1] - u[1] * v[0],
+        ]
+    };
+    let dv = [va[0] - vb[0], va[1] - vb[1], va[2] - vb[2]];
+    let dp = [pa[0] - pb[0], pa[1] - pb[1], pa[2] - pb[2]];
+    let (ca, cb) = (cross(pa, va), cross(pb, vb));
+    let rhs = [ca[0] - cb[0], ca[1] - cb[1], ca[2] - cb[2]];
+    [
+        [0, dv[2], -dv[1], 0, -dp[2], dp[1], rhs[0]],
+        [-dv[2], 0, dv[0], dp[2], 0, -dp[0], rhs[1]],
+        [dv[1], -dv[0], 0, -dp[1], dp[0], 0, rhs[2]],
+    ]
+}
+
+/// Why no rock could be thrown to hit every hailstone.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+enum RockError {
+    TooFewHailstones {
+        count: usize,
+    },
+    /// The hailstones do not pin down a single trajectory.
+    Underdetermined,
+    /// No single trajectory satisfies every hailstone's equations.
+    Inconsistent,
+    /// The only trajectory has fractional or out-of-range components.
+    NonIntegerRock,
+    Missed {
+        hailstone: usize,
+    },
+    /// The rock meets `hailstone` only at a fractional, zero or negative time.
+    BadTime {
+        hailstone: usize,
+        time: Ratio,
+    },
+}
+
+impl fmt::Display for RockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RockError::TooFewHailstones { count } => {
+                write!(f, "need at least 3 hailstones, found {count}")
+            }
+            RockError::Underdetermined => write!(f, "hailstones allow more than one trajectory"),
+            RockError::Inconsistent => write!(f, "no trajectory passes through every hailstone"),
+            RockError::NonIntegerRock => write!(f, "trajectory is not in whole numbers"),
+            RockError::Missed { hailstone } => write!(f, "rock misses hailstone {hailstone}"),
+            RockError::BadTime { hailstone, time } => {
+                write!(f, "rock hits hailstone {hailstone} at t={time}")
+            }
+        }
+    }
+}
+
+/// Solves the augmented system `rows` by Gaussian elimination, requiring exactly one solution.
+fn gaussian_elimination(rows: &[[i128; 7]]) -> Result<Vec<Ratio>, RockError> {
+    let mut rows = rows
+        .iter()
+        .map(|row| row.iter().map(|&v| Ratio::from(v)).collect())
+        .collect::<Vec<Vec<Ratio>>>();
+    let unknowns = 6;
+    for column in 0..unknowns {
+        let pivot = (column..rows.len())
+            .find(|&r| !rows[r][column].is_zero())
+            .ok_or(RockError::Underdetermined)?;
+        rows.swap(column, pivot);
+        let divisor = rows[column][column].clone();
+        for value in rows[column].iter_mut() {
+            *value = value.clone() / divisor.clone();
+        }
+        for r in 0..rows.len() {
+            if r == column || rows[r][column].is_zero() {
+                continue;
+            }
+            let factor = rows[r][column].clone();
+            let pivot_row = rows[column].clone();
+            for (value, pivot) in rows[r].iter_mut().zip(pivot_row).skip(column) {
+                *value = value.clone() - factor.clone() * pivot;
+            }
+        }
+    }
+    // any rows beyond the sixth must have been reduced to nothing
+    if rows[unknowns..].iter().any(|row| !row[unknowns].is_zero()) {
+        return Err(RockError::Inconsistent);
+    }
+    Ok(rows
+        .into_iter()
+        .take(unknowns)
+        .map(|row| row[unknowns].clone())
+        .collect())
+}
+
+/// A rock trajectory and the time at which it hits each hailstone.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+struct Throw {
+    rock: Snowball,
+    times: Vec<i64>,
+}
+
+impl Snowballs {
+    /// The rock that hits every hailstone, solved from the equations of every pair `(0, i)`.
+    fn throw_rock(&self) -> Result<Throw, RockError> {
+        let hail = &self.snowballs;
+        if hail.len() < 3 {
+            return Err(RockError::TooFewHailstones { count: hail.len() });
+        }
+        let rows = hail[1..]
+            .iter()
+            .flat_map(|h| rock_equations(&hail[0], h))
+            .collect::<Vec<[i128; 7]>>();
+        let solution = gaussian_elimination(&rows)?
+            .iter()
+            .map(|value| value.to_integer().and_then(|v| i64::try_from(v).ok()))
+            .collect::<Option<Vec<i64>>>()
+            .ok_or(RockError::NonIntegerRock)?;
+        let rock = Snowball {
+            px: solution[0],
+            py: solution[1],
+            pz: solution[2],
+            vx: solution[3],
+            vy: solution[4],
+            vz: solution[5],
+        };
+        let mut times = Vec::new();
+        for (hailstone, h) in hail.iter().enumerate() {
+            let time = rock
+                .collision_time(h)
+                .ok_or(RockError::Missed { hailstone })?;
+            match time.to_integer().and_then(|t| i64::try_from(t).ok()) {
+                Some(t) if t > 0 => times.push(t),
+                _ => return Err(RockError::BadTime { hailstone, time }),
+            }
+        }
+        Ok(Throw { rock, times })
+    }
+}
+
+#[cfg(feature = "z3")]
 #[derive(Debug)]
-struct Z3Snowball<'a> {
-    px: Int<'a>,
-    py: Int<'a>,
-    pz: Int<'a>,
-    vx: Int<'a>,
-    vy: Int<'a>,
-    vz: Int<'a>,
+struct Z3Snowball {
+    px: Int,
+    py: Int,
+    pz: Int,
+    vx: Int,
+    vy: Int,
+    vz: Int,
 }
 
+#[cfg(feature = "z3")]
 impl Snowball {
-    fn to_z3<'a>(&self, ctx: &'a Context) -> Z3Snowball<'a> {
+    fn to_z3(self) -> Z3Snowball {
         Z3Snowball {
-            px: Int::from_i64(ctx, self.px as i64),
-            py: Int::from_i64(ctx, self.py as i64),
-            pz: Int::from_i64(ctx, self.pz as i64),
-            vx: Int::from_i64(ctx, self.vx as i64),
-            vy: Int::from_i64(ctx, self.vy as i64),
-            vz: Int::from_i64(ctx, self.vz as i64),
+            px: Int::from_i64(self.px),
+            py: Int::from_i64(self.py),
+            pz: Int::from_i64(self.pz),
+            vx: Int::from_i64(self.vx),
+            vy: Int::from_i64(self.vy),
+            vz: Int::from_i64(self.vz),
         }
     }
 }
 
-fn part2(snowballs: &Snowballs) -> usize {
-    let cfg = Config::new();
-    let ctx = Context::new(&cfg);
-    let solver = Solver::new(&ctx);
+fn part2(snowballs: &Snowballs) -> Result<i64, RockError> {
+    let rock = snowballs.throw_rock()?.rock;
+    Ok(rock.px + rock.py + rock.pz)
+}
+
+/// The original Z3 formulation, kept to cross-check the linear solver.
+#[cfg(feature = "z3")]
+fn part2_z3(snowballs: &Snowballs) -> Option<i64> {
+    let solver = Solver::new();
 
-    let rock_px = Int::new_const(&ctx, "rock_px");
-    let rock_py = Int::new_const(&ctx, "rock_py");
-    let rock_pz = Int::new_const(&ctx, "rock_pz");
-    let rock_vx = Int::new_const(&ctx, "rock_vx");
-    let rock_vy = Int::new_const(&ctx, "rock_vy");
-    let rock_vz = Int::new_const(&ctx, "rock_vz");
-    let zero = Int::from_i64(&ctx, 0);
+    let rock_px = Int::new_const("rock_px");
+    let rock_py = Int::new_const("rock_py");
+    let rock_pz = Int::new_const("rock_pz");
+    let rock_vx = Int::new_const("rock_vx");
+    let rock_vy = Int::new_const("rock_vy");
+    let rock_vz = Int::new_const("rock_vz");
+    let zero = Int::from_i64(0);
 
     let selected = snowballs.snowballs.iter().take(3);
 
-    for (i, hail) in (0..).zip(selected.map(|h| h.to_z3(&ctx))) {
-        let t = Int::new_const(&ctx, format!("t{}", i));
-        solver.assert(&t.gt(&zero));
-        solver.assert(&(&rock_px + &rock_vx * &t)._eq(&(hail.px + hail.vx * &t)));
-        solver.assert(&(&rock_py + &rock_vy * &t)._eq(&(hail.py + hail.vy * &t)));
-        solver.assert(&(&rock_pz + &rock_vz * &t)._eq(&(hail.pz + hail.vz * &t)));
+    for (i, hail) in (0..).zip(selected.map(|h| h.to_z3())) {
+        let t = Int::new_const(format!("t{}", i));
+        solver.assert(t.gt(&zero));
+        solver.assert((&rock_px + &rock_vx * &t).eq(hail.px + hail.vx * &t));
+        solver.assert((&rock_py + &rock_vy * &t).eq(hail.py + hail.vy * &t));
+        solver.assert((&rock_pz + &rock_vz * &t).eq(hail.pz + hail.vz * &t));
+    }
+    match (solver.check(), solver.get_model()) {
+        (Sat, Some(model)) => model.eval(&(rock_px + rock_py + rock_pz), true)?.as_i64(),
+        _ => None,
     }
-    let res = if let (Sat, Some(model)) = (solver.check(), solver.get_model()) {
-        model
-            .eval(&(rock_px + rock_py + rock_pz), true)
-            .unwrap()
-            .as_u64()
-            .unwrap() as usize
-    } else {
-        0
-    };
-    res
 }
 
 fn main() {
     let input = fs::read_to_string("input.txt").expect("input.txt should exist");
     let mut snowballs = Snowballs::from(&input);
-    snowballs.min = 200000000000000f64;
-    snowballs.max = 400000000000000f64;
+    snowballs.min = 200000000000000;
+    snowballs.max = 400000000000000;
+    match env::args().nth(1).as_deref() {
+        Some("report") => {
+            for (a, b, crossing) in snowballs.crossings() {
+                println!("hailstones {a} and {b} {}", describe(&crossing));
+            }
+            return;
+        }
+        Some("rock") => {
+            match snowballs.throw_rock() {
+                Ok(Throw { rock, times }) => {
+                    println!(
+                        "rock: {}, {}, {} @ {}, {}, {}",
+                        rock.px, rock.py, rock.pz, rock.vx, rock.vy, rock.vz
+                    );
+                    for (hailstone, t) in times.iter().enumerate() {
+                        println!("hailstone {hailstone} hit at t={t}");
+                    }
+                }
+                Err(e) => println!("{e}"),
+            }
+            return;
+        }
+        _ => {}
+    }
     println!("{}", part1(&snowballs)); //12015
-    println!("{}", part2(&snowballs));
+    match part2(&snowballs) {
+        Ok(sum) => println!("{sum}"),
+        Err(e) => println!("{e}"),
+    }
+    #[cfg(feature = "z3")]
+    println!("z3: {:?}", part2_z3(&snowballs));
 }
 
 #[cfg(test)]
@@ -197,15 +444,64 @@ mod tests {
     fn test_p1() {
         let input = fs::read_to_string("test.txt").expect("test.txt should exist");
         let mut snowballs = Snowballs::from(&input);
-        snowballs.min = 7f64;
-        snowballs.max = 27f64;
+        snowballs.min = 7;
+        snowballs.max = 27;
         assert_eq!(2, part1(&snowballs));
     }
 
+    #[test]
+    fn test_crossings() {
+        let input = fs::read_to_string("test.txt").expect("test.txt should exist");
+        let mut snowballs = Snowballs::from(&input);
+        snowballs.min = 7;
+        snowballs.max = 27;
+        let crossings = snowballs.crossings();
+        assert_eq!(
+            Crossing::Inside {
+                x: Ratio::new(43, 3),
+                y: Ratio::new(46, 3)
+            },
+            crossings[0].2
+        );
+        assert_eq!(Crossing::Parallel, crossings[4].2);
+        assert_eq!(Crossing::Past { a: true, b: false }, crossings[3].2);
+        assert_eq!(Crossing::Past { a: true, b: true }, crossings[6].2);
+    }
+
     #[test]
     fn test_p2() {
         let input = fs::read_to_string("test.txt").expect("test.txt should exist");
         let snowballs = Snowballs::from(&input);
-        assert_eq!(47, part2(&snowballs));
+        assert_eq!(Ok(47), part2(&snowballs));
+    }
+
+    #[test]
+    fn test_throw_rock() {
+        let input = fs::read_to_string("test.txt").expect("test.txt should exist");
+        let snowballs = Snowballs::from(&input);
+        let throw = snowballs.throw_rock().unwrap();
+        assert_eq!(Snowball::from("24, 13, 10 @ -3, 1, 2"), throw.rock);
+        assert_eq!(vec![5, 3, 4, 6, 1], throw.times);
+    }
+
+    #[test]
+    fn test_throw_rock_degenerate() {
+        let input = fs::read_to_string("test.txt").expect("test.txt should exist");
+        let mut snowballs = Snowballs::from(&input);
+        snowballs.snowballs[4].px += 1;
+        assert_eq!(Err(RockError::Inconsistent), snowballs.throw_rock());
+        snowballs.snowballs.truncate(2);
+        assert_eq!(
+            Err(RockError::TooFewHailstones { count: 2 }),
+            snowballs.throw_rock()
+        );
+    }
+
+    #[cfg(feature = "z3")]
+    #[test]
+    fn test_p2_z3() {
+        let input = fs::read_to_string("test.txt").expect("test.txt should exist");
+        let snowballs = Snowballs::from(&input);
+        assert_eq!(part2(&snowballs).ok(), part2_z3(&snowballs));
     }
 }
@@ -0,0 +1,96 @@
+use std::fmt;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+use num::{BigInt, BigRational, ToPrimitive, Zero};
+
+/// An exact fraction. Backed by big integers, as eliminating the rock equations multiplies
+/// several 15-digit coordinates together.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Ratio(BigRational);
+
+impl Ratio {
+    pub fn new(num: i128, den: i128) -> Ratio {
+        assert!(den != 0, "zero denominator");
+        Ratio(BigRational::new(BigInt::from(num), BigInt::from(den)))
+    }
+
+    pub fn zero() -> Ratio {
+        Ratio(BigRational::zero())
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0.is_zero()
+    }
+
+    /// The value as an integer, if it is one that fits.
+    pub fn to_integer(&self) -> Option<i128> {
+        if self.0.is_integer() {
+            self.0.to_integer().to_i128()
+        } else {
+            None
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.0.to_f64().unwrap_or(f64::NAN)
+    }
+}
+
+impl From<i64> for Ratio {
+    fn from(value: i64) -> Self {
+        Ratio(BigRational::from_integer(BigInt::from(value)))
+    }
+}
+
+impl From<i128> for Ratio {
+    fn from(value: i128) -> Self {
+        Ratio(BigRational::from_integer(BigInt::from(value)))
+    }
+}
+
+impl Add for Ratio {
+    type Output = Ratio;
+
+    fn add(self, rhs: Ratio) -> Ratio {
+        Ratio(self.0 + rhs.0)
+    }
+}
+
+impl Sub for Ratio {
+    type Output = Ratio;
+
+    fn sub(self, rhs: Ratio) -> Ratio {
+        Ratio(self.0 - rhs.0)
+    }
+}
+
+impl Neg for Ratio {
+    type Output = Ratio;
+
+    fn neg(self) -> Ratio {
+        Ratio(-self.0)
+    }
+}
+
+impl Mul for Ratio {
+    type Output = Ratio;
+
+    fn mul(self, rhs: Ratio) -> Ratio {
+        Ratio(self.0 * rhs.0)
+    }
+}
+
+impl Div for Ratio {
+    type Output = Ratio;
+
+    fn div(self, rhs: Ratio) -> Ratio {
+        assert!(!rhs.is_zero(), "division by zero");
+        Ratio(self.0 / rhs.0)
+    }
+}
+
+impl fmt::Display for Ratio {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
@@ -1,42 +1,304 @@
-use std::collections::HashSet;
-use std::fs;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Write;
+use std::{env, fs};
 
 use petgraph::algo::connected_components;
 use petgraph::prelude::*;
 
-fn part1(input: &str) -> usize {
-    let mut graph = UnGraphMap::<&str, usize>::new();
-    for line in input.lines() {
-        let mut splits = line.split(": ");
-        let node = splits.next().unwrap();
-        for neighbour in splits.next().unwrap().split(" ") {
-            graph.add_edge(node, neighbour, 0);
-        }
-    }
-    // transform the input file and muck with it as an SVG to find the 3 edges
-    graph.remove_edge("bqq", "rxt");
-    graph.remove_edge("btp", "qxr");
-    graph.remove_edge("bgl", "vfx");
-    println!("{}", connected_components(&graph));
-    let mut sizes = HashSet::new();
-    for node in graph.nodes() {
-        let mut queue = Vec::new();
-        queue.push(node);
+/// How many wires have to be disconnected to split the machine in two.
+const WIRES: usize = 3;
+
+const COLOURS: [&str; 2] = ["lightblue", "lightsalmon"];
+
+#[derive(Clone, Debug)]
+struct Wiring<'a> {
+    graph: UnGraphMap<&'a str, ()>,
+}
+
+impl<'a> From<&'a String> for Wiring<'a> {
+    fn from(value: &'a String) -> Self {
+        let mut graph = UnGraphMap::new();
+        for line in value.lines() {
+            let mut splits = line.split(": ");
+            let node = splits.next().unwrap();
+            for neighbour in splits.next().unwrap().split(' ') {
+                graph.add_edge(node, neighbour, ());
+            }
+        }
+        Wiring { graph }
+    }
+}
+
+impl<'a> Wiring<'a> {
+    /// A set of exactly `size` wires whose removal splits the graph in two, found by looking
+    /// for a component that only `size` edge-disjoint paths connect to the first one.
+    fn min_cut(&self, size: usize) -> Option<Vec<(&'a str, &'a str)>> {
+        let nodes = self.graph.nodes().collect::<Vec<&str>>();
+        let index = nodes
+            .iter()
+            .enumerate()
+            .map(|(i, &node)| (node, i))
+            .collect::<HashMap<&str, usize>>();
+        let edges = self
+            .graph
+            .all_edges()
+            .map(|(a, b, _)| (index[a], index[b]))
+            .collect::<Vec<(usize, usize)>>();
+        let mut adjacent = vec![Vec::new(); nodes.len()];
+        for (e, &(a, b)) in edges.iter().enumerate() {
+            adjacent[a].push((b, e));
+            adjacent[b].push((a, e));
+        }
+        for sink in 1..nodes.len() {
+            // flow[e] is +1 when a path uses edge e from its first to its second end
+            let mut flow = vec![0i8; edges.len()];
+            let mut paths = 0;
+            let reachable = loop {
+                let mut previous = vec![None; nodes.len()];
+                let mut seen = vec![false; nodes.len()];
+                seen[0] = true;
+                let mut queue = VecDeque::from([0]);
+                while let Some(n) = queue.pop_front() {
+                    for &(m, e) in &adjacent[n] {
+                        let forward = edges[e].0 == n;
+                        let spare = if forward { flow[e] < 1 } else { flow[e] > -1 };
+                        if spare && !seen[m] {
+                            seen[m] = true;
+                            previous[m] = Some((n, e));
+                            queue.push_back(m);
+                        }
+                    }
+                }
+                if !seen[sink] || paths > size {
+                    break seen;
+                }
+                let mut n = sink;
+                while let Some((from, e)) = previous[n] {
+                    flow[e] += if edges[e].0 == from { 1 } else { -1 };
+                    n = from;
+                }
+                paths += 1;
+            };
+            if paths == size {
+                return Some(
+                    edges
+                        .iter()
+                        .filter(|&&(a, b)| reachable[a] != reachable[b])
+                        .map(|&(a, b)| (nodes[a], nodes[b]))
+                        .collect(),
+                );
+            }
+        }
+        None
+    }
+
+    /// The two groups of components left once `cut` is removed, if there are exactly two.
+    fn partition(&self, cut: &[(&str, &str)]) -> Option<[HashSet<&'a str>; 2]> {
+        let mut graph = self.graph.clone();
+        let wires = self
+            .graph
+            .all_edges()
+            .filter(|&(a, b, _)| Self::is_cut(cut, a, b))
+            .map(|(a, b, _)| (a, b))
+            .collect::<Vec<_>>();
+        if wires.len() != cut.len() {
+            return None;
+        }
+        for (a, b) in wires {
+            graph.remove_edge(a, b);
+        }
+        if connected_components(&graph) != 2 {
+            return None;
+        }
+        let start = graph.nodes().next()?;
+        let mut queue = vec![start];
         let mut visited = HashSet::new();
         while let Some(n) = queue.pop() {
-            visited.insert(n);
-            for neighbour in graph.neighbors(n) {
-                if !visited.contains(neighbour) {
-                    queue.push(neighbour);
+            if visited.insert(n) {
+                queue.extend(graph.neighbors(n).filter(|m| !visited.contains(m)));
+            }
+        }
+        let rest = graph.nodes().filter(|n| !visited.contains(n)).collect();
+        Some([visited, rest])
+    }
+
+    /// Which side of `partition` a component is on.
+    fn side(partition: &Option<[HashSet<&str>; 2]>, node: &str) -> Option<usize> {
+        partition
+            .as_ref()
+            .map(|sides| if sides[0].contains(node) { 0 } else { 1 })
+    }
+
+    fn is_cut(cut: &[(&str, &str)], a: &str, b: &str) -> bool {
+        cut.iter()
+            .any(|&(x, y)| (x, y) == (a, b) || (x, y) == (b, a))
+    }
+
+    /// The graph in DOT. When `cut` splits it in two, each side is filled with its own colour
+    /// and the cut edges are drawn thick and red.
+    fn to_dot(&self, cut: &[(&str, &str)]) -> String {
+        let partition = self.partition(cut);
+        let mut dot = String::from("graph wiring {\n    node [style=filled];\n");
+        for node in self.graph.nodes() {
+            match Self::side(&partition, node) {
+                Some(side) => {
+                    writeln!(dot, "    \"{node}\" [fillcolor={}];", COLOURS[side]).unwrap()
                 }
+                None => writeln!(dot, "    \"{node}\";").unwrap(),
             }
         }
-        sizes.insert(visited.len());
+        for (a, b, _) in self.graph.all_edges() {
+            if partition.is_some() && Self::is_cut(cut, a, b) {
+                writeln!(dot, "    \"{a}\" -- \"{b}\" [color=red, penwidth=3];").unwrap();
+            } else {
+                writeln!(dot, "    \"{a}\" -- \"{b}\";").unwrap();
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// The graph in GraphML, with the same colouring as [`Wiring::to_dot`] recorded as `side`,
+    /// `colour` and `cut` attributes.
+    fn to_graphml(&self, cut: &[(&str, &str)]) -> String {
+        let partition = self.partition(cut);
+        let mut xml = String::from(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+            "  <key id=\"side\" for=\"node\" attr.name=\"side\" attr.type=\"int\"/>\n",
+            "  <key id=\"colour\" for=\"node\" attr.name=\"colour\" attr.type=\"string\"/>\n",
+            "  <key id=\"cut\" for=\"edge\" attr.name=\"cut\" attr.type=\"boolean\">\n",
+            "    <default>false</default>\n",
+            "  </key>\n",
+            "  <graph id=\"wiring\" edgedefault=\"undirected\">\n",
+        ));
+        for node in self.graph.nodes() {
+            match Self::side(&partition, node) {
+                Some(side) => writeln!(
+                    xml,
+                    "    <node id=\"{node}\"><data key=\"side\">{side}</data>\
+                     <data key=\"colour\">{}</data></node>",
+                    COLOURS[side]
+                )
+                .unwrap(),
+                None => writeln!(xml, "    <node id=\"{node}\"/>").unwrap(),
+            }
+        }
+        for (a, b, _) in self.graph.all_edges() {
+            if partition.is_some() && Self::is_cut(cut, a, b) {
+                writeln!(
+                    xml,
+                    "    <edge source=\"{a}\" target=\"{b}\"><data key=\"cut\">true</data></edge>"
+                )
+                .unwrap();
+            } else {
+                writeln!(xml, "    <edge source=\"{a}\" target=\"{b}\"/>").unwrap();
+            }
+        }
+        xml.push_str("  </graph>\n</graphml>\n");
+        xml
     }
-    sizes.into_iter().reduce(|a, b| a * b).unwrap()
+}
+
+/// Parses `a/b,c/d,e/f` into the wires to cut.
+fn parse_cut(value: &str) -> Result<Vec<(&str, &str)>, String> {
+    value
+        .split(',')
+        .map(|wire| {
+            wire.split_once('/')
+                .ok_or_else(|| format!("wire {wire} should be written as a/b"))
+        })
+        .collect()
+}
+
+fn part1(input: &String) -> usize {
+    let wiring = Wiring::from(input);
+    let cut = wiring
+        .min_cut(WIRES)
+        .expect("wiring should split in two by cutting three wires");
+    let [a, b] = wiring
+        .partition(&cut)
+        .expect("cut should split the wiring in two");
+    a.len() * b.len()
 }
 
 fn main() {
     let input = fs::read_to_string("input.txt").expect("input.txt should exist");
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    if args.first().map(String::as_str) == Some("export") {
+        const USAGE: &str = "usage: export <dot|graphml> [file] [--cut a/b,c/d,e/f]";
+        let mut positional = Vec::new();
+        let mut cut = None;
+        let mut iter = args.iter().skip(1);
+        while let Some(arg) = iter.next() {
+            if arg == "--cut" {
+                match iter.next().map(|wires| parse_cut(wires)) {
+                    Some(Ok(wires)) => cut = Some(wires),
+                    _ => {
+                        println!("{USAGE}");
+                        return;
+                    }
+                }
+            } else {
+                positional.push(arg.as_str());
+            }
+        }
+        let wiring = Wiring::from(&input);
+        let cut = cut.unwrap_or_else(|| wiring.min_cut(WIRES).unwrap_or_default());
+        let output = match positional.as_slice() {
+            ["dot"] | ["dot", _] => wiring.to_dot(&cut),
+            ["graphml"] | ["graphml", _] => wiring.to_graphml(&cut),
+            _ => {
+                println!("{USAGE}");
+                return;
+            }
+        };
+        match positional.get(1) {
+            Some(file) => fs::write(file, output).expect("output should be writable"),
+            None => print!("{output}"),
+        }
+        return;
+    }
     println!("{}", part1(&input));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_CUT: [(&str, &str); 3] = [("hfx", "pzl"), ("bvb", "cmg"), ("nvd", "jqt")];
+
+    #[test]
+    fn test_p1() {
+        let input = fs::read_to_string("test.txt").expect("test.txt should exist");
+        assert_eq!(54, part1(&input));
+    }
+
+    #[test]
+    fn test_min_cut() {
+        let input = fs::read_to_string("test.txt").expect("test.txt should exist");
+        let wiring = Wiring::from(&input);
+        let cut = wiring.min_cut(WIRES).unwrap();
+        assert_eq!(3, cut.len());
+        assert!(cut.iter().all(|&(a, b)| Wiring::is_cut(&TEST_CUT, a, b)));
+        assert!(wiring.min_cut(2).is_none());
+    }
+
+    #[test]
+    fn test_partition() {
+        let input = fs::read_to_string("test.txt").expect("test.txt should exist");
+        let wiring = Wiring::from(&input);
+        let [a, b] = wiring.partition(&TEST_CUT).unwrap();
+        assert_eq!(54, a.len() * b.len());
+        assert!(wiring.partition(&TEST_CUT[..2]).is_none());
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let input = fs::read_to_string("test.txt").expect("test.txt should exist");
+        let wiring = Wiring::from(&input);
+        let dot = wiring.to_dot(&TEST_CUT);
+        assert_eq!(3, dot.matches("color=red").count());
+        assert!(!wiring.to_dot(&[]).contains("fillcolor"));
+    }
+}